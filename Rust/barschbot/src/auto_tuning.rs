@@ -4,7 +4,7 @@ use num_bigint::BigInt;
 use num_traits::{Zero, One, ToPrimitive};
 use rayon::iter::{IntoParallelRefMutIterator, ParallelIterator};
 
-use crate::{endgame_table::EndgameTable, bb_settings::{BBSettings, FactorName, self}, opening_book::OpeningBook, game::{GameState, Game}, match_handler::{play_bot_game, barsch_vs_sf, self}};
+use crate::{endgame_table::EndgameTable, bb_settings::{BBSettings, FactorName, SearchParameter, self}, opening_book::OpeningBook, game::{GameState, Game}, match_handler::{play_bot_game, barsch_vs_sf, self}};
 
 const THREAD_COUNT: usize = 14;
 
@@ -67,7 +67,7 @@ pub fn print_confidence(wins: i32, losses: i32, draws: i32) -> f64 {
     }
 }
 
-fn auto_tune(fens: &&Vec<String>, book: &OpeningBook, table: &EndgameTable, mut start_settings: BBSettings) {
+pub fn auto_tune(fens: &[String], book: &OpeningBook, table: &EndgameTable, mut start_settings: BBSettings) {
     let mut it: usize = FactorName::SafeMobilityP as usize;
     loop {
         let f = bb_settings::ALL_NAMES[it % bb_settings::ALL_NAMES.len()];
@@ -86,7 +86,25 @@ fn auto_tune(fens: &&Vec<String>, book: &OpeningBook, table: &EndgameTable, mut
     }
 }
 
-fn optimize_value_self_play(fens: &&Vec<String>, book: &OpeningBook, table: &EndgameTable, factor_name: bb_settings::FactorName, start_settings: &BBSettings) -> f32 {
+pub fn auto_tune_search(fens: &[String], book: &OpeningBook, table: &EndgameTable, mut start_settings: BBSettings) {
+    let mut it: usize = 0;
+    loop {
+        let p = bb_settings::ALL_SEARCH_PARAMETERS[it % bb_settings::ALL_SEARCH_PARAMETERS.len()];
+        let init = start_settings.get_search_parameter(p);
+        let (better, _) = test_search_range_stock_fish(fens, book, table, p, &start_settings);
+
+        start_settings.set_search_parameter(p, better);
+
+        if init != start_settings.get_search_parameter(p) {
+            println!("Changed {:?}: {} -> {}\n", p, init, better);
+            start_settings.print_search_parameters();
+        }
+
+        it += 1;
+    }
+}
+
+fn optimize_value_self_play(fens: &[String], book: &OpeningBook, table: &EndgameTable, factor_name: bb_settings::FactorName, start_settings: &BBSettings) -> f32 {
     let mut best_settings = start_settings.clone();
 
     //start_settings.eval_factors.print_all();
@@ -112,7 +130,7 @@ fn optimize_value_self_play(fens: &&Vec<String>, book: &OpeningBook, table: &End
     return best_settings.eval_factors.get_value(factor_name);
 }
 
-fn test_eval_range_self_play(fens: &&Vec<String>, book: &OpeningBook, table: &EndgameTable, factor_name: bb_settings::FactorName, start_settings: &BBSettings) -> (f32, f64) {
+fn test_eval_range_self_play(fens: &[String], book: &OpeningBook, table: &EndgameTable, factor_name: bb_settings::FactorName, start_settings: &BBSettings) -> (f32, f64) {
     
     const STEP_COUNT: i32 = 5;
     const RANGE_DIV: f32 = 0.1;
@@ -161,7 +179,7 @@ fn test_eval_range_self_play(fens: &&Vec<String>, book: &OpeningBook, table: &En
     return results[0];
 }
 
-fn test_eval_range_stock_fish(fens: &&Vec<String>, book: &OpeningBook, table: &EndgameTable, factor_name: bb_settings::FactorName, start_settings: &BBSettings) -> (f32, f64) {
+fn test_eval_range_stock_fish(fens: &[String], book: &OpeningBook, table: &EndgameTable, factor_name: bb_settings::FactorName, start_settings: &BBSettings) -> (f32, f64) {
     
     const STEP_COUNT: i32 = 4;
     const RANGE_DIV: f32 = 0.1;
@@ -210,7 +228,66 @@ fn test_eval_range_stock_fish(fens: &&Vec<String>, book: &OpeningBook, table: &E
     return results[0];
 }
 
-fn play_sf_parallel(fens: &&Vec<String>, book: &OpeningBook, table: &EndgameTable, settings: &BBSettings) -> (i32, i32, i32) {
+fn test_search_range_stock_fish(fens: &[String], book: &OpeningBook, table: &EndgameTable, parameter: SearchParameter, start_settings: &BBSettings) -> (f32, f64) {
+    const STEP_COUNT: i32 = 4;
+    const RANGE_DIV: f32 = 0.1;
+
+    let init_value = start_settings.get_search_parameter(parameter);
+
+    //Integer parameters are only moved by one step in each direction
+    let values = if parameter.is_integer() {
+        vec![init_value - 1.0, init_value, init_value + 1.0]
+    }
+    else {
+        let start_val = init_value - RANGE_DIV * init_value;
+        let end_val = init_value + RANGE_DIV * init_value;
+
+        (0..(STEP_COUNT + 1)).map(|i| start_val + (end_val - start_val) * (i as f32 / STEP_COUNT as f32)).collect::<Vec<_>>()
+    };
+
+    //The setter clamps the value, the result is reported for the value actually used
+    return test_values_stock_fish(fens, book, table, start_settings, values, |settings, val| {
+        settings.set_search_parameter(parameter, val);
+        return settings.get_search_parameter(parameter);
+    });
+}
+
+//Plays the fens against stockfish once for every value, set_value applies a value and returns the one that was used
+//Returns the value with the highest likelihood of superiority
+fn test_values_stock_fish(fens: &[String], book: &OpeningBook, table: &EndgameTable, start_settings: &BBSettings, values: Vec<f32>, 
+    set_value: impl Fn(&mut BBSettings, f32) -> f32) -> (f32, f64) {
+    let mut improv = start_settings.clone();
+
+    let mut max_score = 0;
+    let mut results = Vec::new();
+
+    for val in values {
+        println!("Trying value: {}", val);
+        
+        let val = set_value(&mut improv, val);
+
+        let (wins, losses, draws) = play_sf_parallel(fens, book, table, &improv);
+
+        results.push((val, print_confidence(wins, losses, draws)));
+
+        if wins * 2 + draws > max_score {
+            println!("\tNew best value: {}", val);
+
+            max_score = wins * 2 + draws;
+        }
+    }
+
+    println!("Final scores: ");
+    results.sort_unstable_by(|a, b| { return b.1.partial_cmp(&a.1).unwrap() });
+
+    for r in &results {
+        println!("Value: {} -> {}", r.0, r.1);
+    }
+
+    return results[0];
+}
+
+fn play_sf_parallel(fens: &[String], book: &OpeningBook, table: &EndgameTable, settings: &BBSettings) -> (i32, i32, i32) {
     let mut threads = Vec::new();
     let fens_per_thread = fens.len() / THREAD_COUNT;
     let mut reisdue = fens.len() % THREAD_COUNT;
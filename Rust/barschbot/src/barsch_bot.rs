@@ -1,13 +1,26 @@
-use std::{time::Instant, cmp, collections::HashMap};
+use std::{time::Instant, cmp, sync::atomic::{AtomicBool, AtomicU64, Ordering}, thread};
 
 use arrayvec::ArrayVec;
 use num::complex::ComplexFloat;
-use rand::seq::SliceRandom;
 
 use crate::{game::{Game, GameState}, chess_move::{ChessMove, self, NULL_MOVE}, piece_type::PieceType, bit_board::{BitBoard, self}, 
-    evaluation::*, endgame_table::{self, EndgameTable, UNDEFINED, BoardState}, bb_settings::{self, BBSettings}, opening_book::OpeningBook, bitboard_helper};
-
-const MAX_VALUE: f32 =  f32::INFINITY;
+    evaluation::*, endgame_table::{self, EndgameTable, UNDEFINED, BoardState}, bb_settings::{self, BBSettings}, opening_book::OpeningBook, bitboard_helper,
+    time_manager::{TimeControl, TimeManager}, transposition_table::{TranspositionTable, Bound}, move_ordering::{self, MoveOrdering}, move_picker::MovePicker, score::Score, extensions::{Extensions, FEW_REPLIES}};
+
+//Deepest iteration a timed search may start
+pub const MAX_SEARCH_DEPTH: u8 = 64;
+//Upper limit for the threads setting
+pub const MAX_THREADS: u8 = 64;
+//Upper limit for the multi pv setting
+pub const MAX_MULTI_PV: u8 = 64;
+//Width of a zero window search in centipawns
+const NULL_WINDOW: i32 = 1;
+//Aspiration windows wider than this many centipawns are replaced by a full window
+const MAX_ASPIRATION_WINDOW: i32 = 800;
+//Depth saved by searching the null move
+const NULL_MOVE_REDUCTION: u8 = 2;
+//Nodes between two checks of the hard time limit, must be a power of two
+const ABORT_CHECK_INTERVAL: u64 = 1024;
 
 pub struct Stats {
     pub nodes: u64,
@@ -26,21 +39,180 @@ impl Stats {
     }
 }
 
-pub fn get_best_move(game: &mut Game, table: &EndgameTable, bb_settings: &BBSettings, book: &OpeningBook) -> ChessMove{
+//Reported for every line of every finished iteration
+pub struct SearchInfo {
+    pub depth: u8,
+    //Rank of the line starting at 1, see BBSettings::multi_pv
+    pub multi_pv: usize,
+    pub score: Score,
+    pub nodes: u64,
+    pub time_ms: u128,
+    pub hashfull: u32,
+    pub pv: Vec<ChessMove>,
+    pub san_pv: String,
+}
+
+impl SearchInfo {
+    pub fn nps(&self) -> u64 {
+        return (self.nodes as u128 * 1000 / cmp::max(self.time_ms, 1)) as u64;
+    }
+}
+
+//Triangular table, row ply holds the best line found so far from the node at that ply
+pub struct PvTable {
+    moves: [[ChessMove; move_ordering::MAX_PLY]; move_ordering::MAX_PLY],
+    lengths: [usize; move_ordering::MAX_PLY],
+}
+
+impl PvTable {
+    pub fn new() -> PvTable {
+        return PvTable { moves: [[NULL_MOVE; move_ordering::MAX_PLY]; move_ordering::MAX_PLY], lengths: [0; move_ordering::MAX_PLY] };
+    }
+
+    pub fn clear(&mut self, ply: u8) {
+        if (ply as usize) < move_ordering::MAX_PLY {
+            self.lengths[ply as usize] = 0;
+        }
+    }
+
+    //m became the best move at ply, it is followed by the line of the child node
+    pub fn update(&mut self, ply: u8, m: ChessMove) {
+        let ply = ply as usize;
+
+        if ply >= move_ordering::MAX_PLY {
+            return;
+        }
+
+        let child_length = if ply + 1 < move_ordering::MAX_PLY { self.lengths[ply + 1] } else { 0 };
+        let length = (child_length + 1).min(move_ordering::MAX_PLY - ply);
+
+        self.moves[ply][0] = m;
+
+        for i in 1..length {
+            self.moves[ply][i] = self.moves[ply + 1][i - 1];
+        }
+
+        self.lengths[ply] = length;
+    }
+
+    pub fn get_root_line(&self) -> Vec<ChessMove> {
+        return self.moves[0][..self.lengths[0]].to_vec();
+    }
+}
+
+//One root move of a multi pv analysis
+#[derive(Clone)]
+pub struct AnalysisLine {
+    pub chess_move: ChessMove,
+    pub score: Score,
+    pub pv: Vec<ChessMove>,
+}
+
+//Set from outside while a search runs
+pub struct SearchSignals {
+    pub stop: AtomicBool,
+    //While set the search ignores its limits, a ponderhit clears it and starts the clock
+    pub ponder: AtomicBool,
+    //Nodes of every thread using these signals, published at each clock check
+    pub nodes: AtomicU64,
+}
+
+impl SearchSignals {
+    pub fn new() -> SearchSignals {
+        return SearchSignals { stop: AtomicBool::new(false), ponder: AtomicBool::new(false), nodes: AtomicU64::new(0) };
+    }
+}
+
+pub struct SearchContext<'a> {
+    pub stats: Stats,
+    pub ordering: MoveOrdering,
+    pub time_manager: TimeManager,
+    pub signals: &'a SearchSignals,
+    pub pondering: bool,
+    pub aborted: bool,
+    //Part of stats.nodes already added to signals.nodes
+    published_nodes: u64,
+    pub pv: Box<PvTable>,
+    //Root moves skipped because an earlier multi pv line already covers them
+    pub excluded_root_moves: Vec<ChessMove>,
+}
+
+impl<'a> SearchContext<'a> {
+    pub fn new(time_manager: TimeManager, signals: &'a SearchSignals) -> SearchContext<'a> {
+        let pondering = signals.ponder.load(Ordering::Relaxed);
+
+        return SearchContext { stats: Stats::new(), ordering: MoveOrdering::new(), time_manager, signals, pondering, aborted: false, published_nodes: 0, pv: Box::new(PvTable::new()), excluded_root_moves: Vec::new() };
+    }
+
+    //The time used while pondering is free, on a ponderhit the search continues as a normal timed one
+    pub fn check_ponderhit(&mut self) {
+        if self.pondering && !self.signals.ponder.load(Ordering::Relaxed) {
+            self.pondering = false;
+            self.time_manager.restart();
+        }
+    }
+
+    fn publish_nodes(&mut self) {
+        self.signals.nodes.fetch_add(self.stats.nodes - self.published_nodes, Ordering::Relaxed);
+        self.published_nodes = self.stats.nodes;
+    }
+
+    //Only looks at the clock every few nodes, stays aborted once a limit was hit or a stop was requested
+    pub fn should_abort(&mut self) -> bool {
+        //Checked on every node so node limited searches stop at exactly the same point
+        if !self.aborted && self.time_manager.node_limit_reached(self.stats.nodes) {
+            self.aborted = true;
+        }
+
+        if !self.aborted && self.stats.nodes & (ABORT_CHECK_INTERVAL - 1) == 0 {
+            self.check_ponderhit();
+            self.publish_nodes();
+
+            if self.signals.stop.load(Ordering::Relaxed) || (!self.pondering && self.time_manager.hard_limit_reached()) {
+                self.aborted = true;
+            }
+        }
+
+        return self.aborted;
+    }
+}
+
+pub fn get_best_move(game: &mut Game, table: &EndgameTable, bb_settings: &BBSettings, book: &OpeningBook, tt: &mut TranspositionTable) -> ChessMove{
+    let signals = SearchSignals::new();
+
+    return search(game, table, bb_settings, book, tt, &TimeControl::none(), &signals, &mut |_| ()).chess_move;
+}
+
+//Ranked analysis of the best bb_settings.multi_pv moves, opening book and endgame table moves are not considered at the root
+pub fn get_best_lines(game: &mut Game, table: &EndgameTable, bb_settings: &BBSettings, tt: &mut TranspositionTable) -> Vec<AnalysisLine> {
+    let signals = SearchSignals::new();
+
+    return analyze(game, table, bb_settings, tt, TimeManager::infinite(), &signals, &mut |_| ());
+}
+
+//Stops early once signals.stop is set, on_info is called after every finished iteration
+//Book and endgame table moves come without a search, their line only holds the move itself
+pub fn search(game: &mut Game, table: &EndgameTable, bb_settings: &BBSettings, book: &OpeningBook, tt: &mut TranspositionTable, time_control: &TimeControl, 
+    signals: &SearchSignals, on_info: &mut dyn FnMut(&SearchInfo)) -> AnalysisLine {
     //println!("Looking for best move");
-    let om = book.get_move(game.get_board().get_zoberist_hash());
+    let om = book.get_move(&game.get_board());
 
-    if om != NULL_MOVE {
+    //An infinite analysis always searches so it can report its lines
+    if om != NULL_MOVE && !time_control.infinite {
         //println!("Book move");
-        return om;
+        return AnalysisLine { chess_move: om, score: Score::DRAW, pv: vec![om] };
     }
 
-    if bb_settings.end_game_table && game.get_board().get_all_piece_count() <= table.max_piece_count as u32 {
+    if bb_settings.end_game_table && !time_control.infinite && game.get_board().get_all_piece_count() <= table.max_piece_count as u32 {
         //println!("Endgame move");
-        return end_game_move(game, table);
+        let m = end_game_move(game, table);
+
+        return AnalysisLine { chess_move: m, score: Score::DRAW, pv: vec![m] };
     }
     
-    return iterative_deepening(game, table, bb_settings).0; 
+    let time_manager = TimeManager::new(time_control, game.is_whites_turn());
+
+    return iterative_deepening(game, table, bb_settings, tt, time_manager, signals, on_info); 
 }
 
 pub fn end_game_move(game: &mut Game, table: &EndgameTable) -> ChessMove {
@@ -85,89 +257,277 @@ pub fn end_game_move(game: &mut Game, table: &EndgameTable) -> ChessMove {
     return best_move;
 }
 
-pub fn get_relative_endgame_eval(board: &BitBoard, table: &EndgameTable) -> (f32, GameState) {
-    if board.get_all_piece_count() <= table.max_piece_count as u32 {
+//Exact score from the endgame table relative to the side to move, the table counts plies until mate as 127 - |score|
+pub fn get_relative_endgame_eval(board: &BitBoard, table: &EndgameTable, ply: u8) -> Option<Score> {
+    if board.get_all_piece_count() > table.max_piece_count as u32 {
+        return None;
+    }
 
-        //println!("This should not happen {}", table.max_piece_count);
-        let score = table.get_score(&board);
-        
-        let mut res = 0.0;
-        let mut gs = GameState::Undecided;
-        if score == 0 {
-            res = 0.0;
-            gs = GameState::InsuffMaterial;
-        }
-        else {
-            gs = if score > 0 { GameState::BlackCheckmate } else { GameState::WhiteCheckmate };
+    let score = table.get_score(&board);
 
-            if board.is_whites_turn() {
-                res = if score > 0 { CHECKMATE_VALUE } else { -CHECKMATE_VALUE };
-            }
-            else {
-                res = if score < 0 { CHECKMATE_VALUE } else { -CHECKMATE_VALUE };
-            }
+    if score == 0 {
+        return Some(Score::DRAW);
+    }
 
-            for i in 0..(127 - score.abs()) {
-                res *= 0.999;
-            }
-        }
+    let mate_ply = ply as u32 + (127 - score.abs() as i32) as u32;
+
+    //Positive scores are wins for white
+    if (score > 0) == board.is_whites_turn() {
+        return Some(Score::mate_in(mate_ply));
+    }
+
+    return Some(Score::mated_in(mate_ply));
+}
+
+//Score of a finished game at the given ply, None while the game is still running
+fn get_terminal_score(game: &mut Game, ply: u8) -> Option<Score> {
+    let game_state = game.get_game_state();
+
+    if game_state == GameState::Undecided {
+        return None;
+    }
 
-        return (res, gs);
+    //Only the side to move can be checkmated
+    if game_state.is_checkmate() {
+        return Some(Score::mated_in(ply as u32));
     }
 
-    return (0.0, GameState::Undecided);
+    return Some(Score::DRAW);
 }
 
-pub fn iterative_deepening(game: &mut Game, table: &EndgameTable, bb_settings: &BBSettings) -> (ChessMove, f32) {
+fn evaluate(game: &mut Game, settings: &BBSettings) -> Score {
+    return Score::from_pawns(static_eval(game, &settings.eval_factors, false).0);
+}
+
+//The best line of the last finished iteration
+pub fn iterative_deepening(game: &mut Game, table: &EndgameTable, bb_settings: &BBSettings, tt: &mut TranspositionTable, time_manager: TimeManager, 
+    signals: &SearchSignals, on_info: &mut dyn FnMut(&SearchInfo)) -> AnalysisLine {
+    return analyze(game, table, bb_settings, tt, time_manager, signals, on_info).swap_remove(0);
+}
+
+//The best BBSettings::multi_pv root moves, best first
+//Lazy SMP, with more than one thread the helpers search the same position and only share their results through the transposition table
+pub fn analyze(game: &mut Game, table: &EndgameTable, bb_settings: &BBSettings, tt: &mut TranspositionTable, time_manager: TimeManager, 
+    signals: &SearchSignals, on_info: &mut dyn FnMut(&SearchInfo)) -> Vec<AnalysisLine> {
+    if bb_settings.deterministic {
+        tt.clear();
+
+        let tt: &TranspositionTable = tt;
+
+        return deepen(game, table, bb_settings, tt, time_manager.without_clock(), signals, None, 0, on_info);
+    }
+
+    tt.new_search();
+
+    let tt: &TranspositionTable = tt;
+
+    if bb_settings.threads <= 1 {
+        return deepen(game, table, bb_settings, tt, time_manager, signals, None, 0, on_info);
+    }
+
+    //Helpers run until the main thread has its result
+    let helper_signals = SearchSignals::new();
+
+    return thread::scope(|scope| {
+        for thread_index in 1..bb_settings.threads {
+            let mut helper_game = game.clone();
+            let helper_signals = &helper_signals;
+
+            scope.spawn(move || {
+                deepen(&mut helper_game, table, bb_settings, tt, TimeManager::infinite(), helper_signals, None, thread_index, &mut |_| ());
+            });
+        }
+
+        let result = deepen(game, table, bb_settings, tt, time_manager, signals, Some(&helper_signals), 0, on_info);
+
+        helper_signals.stop.store(true, Ordering::Relaxed);
+
+        return result;
+    });
+}
+
+//Thread 0 is the main thread, odd helpers search one ply deeper so the threads spread over different depths
+//The main thread adds the nodes the helpers published to the reported ones
+fn deepen(game: &mut Game, table: &EndgameTable, bb_settings: &BBSettings, tt: &TranspositionTable, time_manager: TimeManager, 
+    signals: &SearchSignals, helper_signals: Option<&SearchSignals>, thread_index: u8, on_info: &mut dyn FnMut(&SearchInfo)) -> Vec<AnalysisLine> {
     const PRINT: bool = false;
     
-    let mut map = HashMap::new();
+    let print = PRINT && thread_index == 0;
+    let depth_offset = thread_index % 2;
     
-    if PRINT {
+    if print {
         println!("Evaluating: {}", game.get_board().get_fen());
         static_eval(game, &bb_settings.eval_factors, true);
     }
 
-    let mut start = Instant::now();
-    let mut pair: (ChessMove, f32, GameState) = (NULL_MOVE, 0.0, GameState::Undecided);
-    let mut stats = Stats::new();
+    let mut ctx = SearchContext::new(time_manager, signals);
 
     let mut md = 1 as u8;
 
-    let mut list = game.get_legal_moves();    
+    let list = game.get_legal_moves();    
 
-    while md <= bb_settings.max_depth || start.elapsed().as_millis() < (bb_settings.min_search_time as u128) {
-        pair = alpha_beta_nega_max(game, -MAX_VALUE, MAX_VALUE,  md, md, bb_settings.max_extensions, table, &mut map, bb_settings, &mut stats);
-        //pair = negation_max(game, i);
-    
-        let duration = start.elapsed();
-        
-        if PRINT {
-            println!("{:?}", duration);
-            print!("Depth: {} Eval: ", md);
-            
-            
-            if pair.2 == GameState::Undecided {
-                print!("{:.3}", pair.1);
+    //Helpers only fill the transposition table, a single line is enough for that
+    let line_count = if thread_index == 0 { (bb_settings.multi_pv as usize).clamp(1, list.len().max(1)) } else { 1 };
+
+    //Fallback in case the first iteration gets aborted
+    let mut lines = vec![AnalysisLine { chess_move: list.first().copied().unwrap_or(NULL_MOVE), score: Score::DRAW, pv: Vec::new() }];
+
+    while md + depth_offset <= MAX_SEARCH_DEPTH && continue_deepening(md + depth_offset, bb_settings, &mut ctx) {
+        let mut iteration_lines: Vec<AnalysisLine> = Vec::new();
+        ctx.excluded_root_moves.clear();
+
+        for k in 0..line_count {
+            let prev_score = lines.get(k).map(|l| l.score).unwrap_or(Score::DRAW);
+            let res = aspiration_search(game, prev_score, md + depth_offset, table, tt, bb_settings, &mut ctx);
+            //pair = negation_max(game, i);
+
+            if ctx.aborted || res.0.is_null_move() {
+                break;
             }
-            else {
-                print!("{:.3}", pair.2.to_string());
+
+            //The pv table is cut short by transposition table cutoffs and empty when the root result came from one
+            let mut line = ctx.pv.get_root_line();
+            if line.first() != Some(&res.0) {
+                line = vec![res.0];
             }
+
+            let pv = get_principal_variation(game, tt, &line, (md + depth_offset) as usize);
+
+            ctx.excluded_root_moves.push(res.0);
+            iteration_lines.push(AnalysisLine { chess_move: res.0, score: res.1, pv });
+        }
+
+        //Results of an unfinished iteration are not trustworthy, without any line the game is already over
+        if ctx.aborted || iteration_lines.is_empty() {
+            break;
+        }
+
+        iteration_lines.sort_by(|a, b| b.score.cmp(&a.score));
+        lines = iteration_lines;
+    
+        let duration = ctx.time_manager.elapsed_ms();
+        let nodes = ctx.stats.nodes + helper_signals.map_or(0, |h| h.nodes.load(Ordering::Relaxed));
+
+        if thread_index == 0 {
+            for (k, line) in lines.iter().enumerate() {
+                on_info(&SearchInfo { 
+                    depth: md, 
+                    multi_pv: k + 1,
+                    score: line.score, 
+                    nodes, 
+                    time_ms: duration, 
+                    hashfull: tt.hashfull(),
+                    pv: line.pv.clone(),
+                    san_pv: game.get_san_line(&line.pv),
+                });
+            }
+        }
+        
+        if print {
+            println!("{} ms", duration);
+            print!("Depth: {} Eval: {}", md, lines[0].score.to_uci());
             
-            println!(" Move: {}", pair.0.get_uci());
+            println!(" PV: {}", game.get_san_line(&lines[0].pv));
         }
     
-        if pair.2.is_checkmate() {
+        //Iterative deepening finds the shortest mate first
+        if lines.iter().all(|l| l.score.is_mate()) {
             break;
         }
         md += 1;
     }
 
-    if PRINT {
-        stats.print();
+    if print {
+        ctx.stats.print();
+    }
+
+    return lines;
+}
+
+//Searches a window around the previous score first and widens it on every fail
+fn aspiration_search(game: &mut Game, prev_score: Score, depth: u8, table: &EndgameTable, tt: &TranspositionTable, settings: &BBSettings, ctx: &mut SearchContext) -> (ChessMove, Score) {
+    let mut window = (settings.aspiration_window_size * 100.0) as i32;
+    
+    let use_window = settings.aspiration_windows && depth > 1 && !prev_score.is_mate();
+    let mut alpha = if use_window { prev_score - window } else { -Score::INFINITY };
+    let mut beta = if use_window { prev_score + window } else { Score::INFINITY };
+
+    loop {
+        let res = alpha_beta_nega_max(game, alpha, beta, depth, 0, Extensions::new(settings), table, tt, settings, ctx);
+
+        if ctx.aborted {
+            return res;
+        }
+
+        let failed_low = res.1 <= alpha && alpha != -Score::INFINITY;
+        let failed_high = res.1 >= beta && beta != Score::INFINITY;
+
+        if !failed_low && !failed_high {
+            return res;
+        }
+
+        window *= 2;
+
+        if failed_low {
+            alpha = if window > MAX_ASPIRATION_WINDOW { -Score::INFINITY } else { prev_score - window };
+        }
+        else {
+            beta = if window > MAX_ASPIRATION_WINDOW { Score::INFINITY } else { prev_score + window };
+        }
+    }
+}
+
+//Follows the given line and continues with the stored best moves once it ends, every move is checked for legality
+fn get_principal_variation(game: &mut Game, tt: &TranspositionTable, line: &[ChessMove], max_length: usize) -> Vec<ChessMove> {
+    let mut pv = Vec::new();
+    let mut next_move = line.first().copied().unwrap_or(NULL_MOVE);
+
+    while pv.len() < max_length && !next_move.is_null_move() {
+        if game.get_game_state() != GameState::Undecided || !game.get_legal_moves().contains(&next_move) {
+            break;
+        }
+
+        game.make_move(next_move);
+        pv.push(next_move);
+
+        next_move = match (line.get(pv.len()), tt.probe(game.get_board().get_zoberist_hash())) {
+            (Some(m), _) => *m,
+            (None, Some(entry)) => entry.best_move.unpack(&game.get_board()),
+            (None, None) => NULL_MOVE,
+        };
+    }
+
+    for _ in 0..pv.len() {
+        game.undo_move();
+    }
+
+    return pv;
+}
+
+fn continue_deepening(depth: u8, bb_settings: &BBSettings, ctx: &mut SearchContext) -> bool {
+    ctx.check_ponderhit();
+
+    let time_manager = &ctx.time_manager;
+
+    if time_manager.node_limit_reached(ctx.stats.nodes) {
+        return false;
+    }
+
+    //The clock only starts with the ponderhit
+    if ctx.pondering {
+        return depth <= bb_settings.max_depth;
+    }
+
+    if time_manager.is_timed() {
+        //Always finish at least one iteration
+        return depth <= bb_settings.max_depth && (depth == 1 || !time_manager.soft_limit_reached());
+    }
+
+    if bb_settings.deterministic {
+        return depth <= bb_settings.max_depth;
     }
 
-    return (pair.0, pair.1);
+    return depth <= bb_settings.max_depth || time_manager.elapsed_ms() < (bb_settings.min_search_time as u128);
 }
 
 fn move_sorter(list: &mut ArrayVec<ChessMove, 200>, prev_best: ChessMove) {
@@ -206,25 +566,26 @@ fn move_sorter(list: &mut ArrayVec<ChessMove, 200>, prev_best: ChessMove) {
     });
 }
 
-pub fn better_move_sorter(list: &mut ArrayVec<ChessMove, 200>, board: &BitBoard, prev_best: ChessMove) {
+pub fn better_move_sorter(list: &mut ArrayVec<ChessMove, 200>, board: &BitBoard, prev_best: ChessMove, ordering: &MoveOrdering, ply: u8, prev_move: ChessMove) {
     const PIECE_VALUES: [i32; 7] = [10, 28, 32, 50, 90, 100, 0];
 
     //board.print();            
     list.sort_by_cached_key(|cm| {
         if *cm == prev_best {
-            return i32::MIN;
+            return (i32::MIN, 0);
         }
         
         let mut sum = 0;
 
-        if cm.is_direct_capture() {
-            sum += PIECE_VALUES[PieceType::from_cpt(cm.capture_piece_type) as usize] 
-            - PIECE_VALUES[PieceType::from_cpt(cm.move_piece_type) as usize] 
-            + 200;
-        }
+        if cm.is_capture() {
+            let see = board.see(*cm);
 
-        if cm.is_en_passant() {
-            sum += 200;
+            //Captures that lose material are tried after all quiet moves
+            if see < 0 {
+                return (0, -see);
+            }
+
+            sum += see / 10 + 200;
         }
 
         sum *= 1000;
@@ -238,13 +599,18 @@ pub fn better_move_sorter(list: &mut ArrayVec<ChessMove, 200>, board: &BitBoard,
 
         //println!("Move: {} sum: {}", cm.get_board_name(&board), sum);
 
-        return -sum;
+        //Quiet moves come after all captures and promotions
+        if MoveOrdering::is_quiet(*cm) {
+            return (-1, -(ordering.get_quiet_score(*cm, ply, prev_move) * 1000 + sum));
+        }
+
+        return (-2, -sum);
     });
 
     //board.print_local_moves(&list);
 }
 
-pub fn best_move_sorter(list: &mut ArrayVec<ChessMove, 200>, game: &mut Game, map: &HashMap<u64, (u8, ChessMove, f32, GameState)>, prev_best: ChessMove, settings: &BBSettings) {
+pub fn best_move_sorter(list: &mut ArrayVec<ChessMove, 200>, game: &mut Game, tt: &TranspositionTable, prev_best: ChessMove, settings: &BBSettings) {
 
 
     list.sort_by_cached_key(|cm| {
@@ -252,157 +618,349 @@ pub fn best_move_sorter(list: &mut ArrayVec<ChessMove, 200>, game: &mut Game, ma
         let hash = game.get_board().get_zoberist_hash();
         let in_check = game.get_board().in_check();
         
-        let mut eval = 0.0;    
-        if map.contains_key(&hash) {
-            eval = -map[&hash].2;
+        let mut eval = Score::DRAW;    
+        if let Some(entry) = tt.probe(hash) {
+            eval = -entry.score;
         }
         else if in_check {
-            eval = -100.0;
+            eval = Score::from_pawns(-100.0);
         }
-        else {
-            eval = static_eval(game, &settings.eval_factors, false).0;
+        else if game.get_game_state() == GameState::Undecided {
+            eval = evaluate(game, settings);
         }
 
         game.undo_move();        
 
-        if eval.is_finite() {
-            return (eval * 1000.0) as i32;
-        }
-        else {
-            return -100000;
-        }
+        return eval.get_value();
     });
 
 
     //board.print_local_moves(&list);
 }
 
-pub fn alpha_beta_nega_max(game: &mut Game, mut alpha: f32, beta: f32, depth_left: u8, max_depth: u8, extensions_left: u8, table: &EndgameTable, map: &mut HashMap<u64, (u8, ChessMove, f32, GameState)>, settings: &BBSettings, stats: &mut Stats) -> (ChessMove, f32, GameState) {        
-    stats.nodes += 1;
-    
+pub fn alpha_beta_nega_max(game: &mut Game, mut alpha: Score, beta: Score, depth_left: u8, ply: u8, extensions: Extensions, table: &EndgameTable, tt: &TranspositionTable, settings: &BBSettings, ctx: &mut SearchContext) -> (ChessMove, Score) {        
+    ctx.pv.clear(ply);
+
+    //The quiescence search counts this node itself
     if depth_left == 0 {
-        stats.qs += 1;
-        let (cm, eval, gs) = quiescence(game, alpha, beta, settings.max_quiescence_depth, table, map, settings);
+        ctx.stats.qs += 1;
+        let (cm, eval) = quiescence(game, alpha, beta, settings.max_quiescence_depth, ply, table, tt, settings, ctx);
 
-        let hash = game.get_board().get_zoberist_hash();
-        if map.contains_key(&hash) {
-            let mut pair = map.get_mut(&hash).unwrap();
-            if depth_left > pair.0 {
-                *pair = (depth_left, cm, eval, gs);
-            }
-        }
-        else {
-            map.insert(hash, (depth_left, cm, eval, gs));
+        if ctx.aborted {
+            return (NULL_MOVE, Score::DRAW);
         }
+
+        tt.store(game.get_board().get_zoberist_hash(), 0, ply, cm, eval, Bound::from_window(eval, alpha, beta));
         
-        return (cm, eval, gs);
+        return (cm, eval);
+    }
+
+    ctx.stats.nodes += 1;
+
+    if ctx.should_abort() {
+        return (NULL_MOVE, Score::DRAW);
     }
 
-    if game.get_game_state() != GameState::Undecided {
-        let pair = static_eval(game, &settings.eval_factors, false);
-        return (chess_move::NULL_MOVE, pair.0, pair.1);
+    if let Some(score) = get_terminal_score(game, ply) {
+        return (chess_move::NULL_MOVE, score);
     }
     
     if settings.end_game_table {
-        let pair = get_relative_endgame_eval(&game.get_board(), table);
-        if pair.1 != GameState::Undecided {
-            return (chess_move::NULL_MOVE, pair.0, pair.1);
+        if let Some(score) = get_relative_endgame_eval(&game.get_board(), table, ply) {
+            return (chess_move::NULL_MOVE, score);
         }
     }
 
+    //The stored root result may be one of the excluded moves
+    let use_tt = ply > 0 || ctx.excluded_root_moves.is_empty();
+
     let hash = game.get_board().get_zoberist_hash();
     let mut hist_move = chess_move::NULL_MOVE;
-    if map.contains_key(&hash) {
-        let (hist_depth_left, m , hist_value, hist_gs) = map[&hash];
+    let tt_entry = tt.probe(hash);
+    if let Some(entry) = tt_entry {
+        hist_move = entry.best_move.unpack(&game.get_board());
+
+        //The root has to return a legal move, fail low entries are stored without one
+        let legal_root_move = ply > 0 || game.get_legal_moves().contains(&hist_move);
 
-        hist_move = m;
-        if hist_depth_left >= depth_left {
-            return (hist_move, hist_value, hist_gs);
+        if use_tt && legal_root_move && entry.depth >= depth_left {
+            if let Some(score) = entry.get_cutoff_score(alpha, beta, ply) {
+                return (hist_move, score);
+            }
         }
     }
 
-    let mut best_move = NULL_MOVE;
-    let mut best_gs = GameState::Undecided;
+    let in_check = game.get_board().in_check();
+    //Zero window nodes only have to prove a bound, the margin based pruning stays out of the principal variation
+    let pv_node = beta.get_value() - alpha.get_value() > NULL_WINDOW;
 
-    let mut list = game.get_legal_moves();
+    let prune_depth = settings.rfp_max_depth.max(settings.razoring_max_depth).max(settings.futility_max_depth);
+    let static_score = if !in_check && !pv_node && ply > 0 && depth_left <= prune_depth { Some(evaluate(game, settings)) } else { None };
+
+    let mut futile = false;
+
+    if let Some(static_score) = static_score {
+        //Still above beta after giving away the margin, the opponent will avoid this position
+        if settings.reverse_futility_pruning && depth_left <= settings.rfp_max_depth && !beta.is_mate()
+            && static_score - BBSettings::get_depth_margin(settings.rfp_margin, depth_left) >= beta {
+            return (NULL_MOVE, beta);
+        }
+
+        //So far below alpha that only captures could help, which the quiescence search covers
+        if settings.razoring && depth_left <= settings.razoring_max_depth && !alpha.is_mate()
+            && static_score + BBSettings::get_depth_margin(settings.razoring_margin, depth_left) <= alpha {
+            let value = quiescence(game, alpha, beta, settings.max_quiescence_depth, ply, table, tt, settings, ctx).1;
+
+            if ctx.aborted {
+                return (NULL_MOVE, Score::DRAW);
+            }
+
+            if value <= alpha {
+                return (NULL_MOVE, alpha);
+            }
+        }
+
+        //Quiet moves are unlikely to make up the margin
+        futile = settings.futility_pruning && depth_left <= settings.futility_max_depth && !alpha.is_mate()
+            && static_score + BBSettings::get_depth_margin(settings.futility_margin, depth_left) <= alpha;
+    }
+
+    if try_null_move(game, beta, depth_left, ply, settings) {
+        game.make_move(NULL_MOVE);
+
+        let reduced_depth = depth_left.saturating_sub(1 + NULL_MOVE_REDUCTION);
+        let value = -alpha_beta_nega_max(game, -beta, -beta + NULL_WINDOW, reduced_depth, ply + 1, extensions, table, tt, settings, ctx).1;
+
+        game.undo_move();
+
+        if ctx.aborted {
+            return (NULL_MOVE, Score::DRAW);
+        }
+
+        //Even passing does not bring the opponent back below beta
+        if value >= beta {
+            ctx.stats.null_move_prunes += 1;
+            tt.store(hash, depth_left, ply, NULL_MOVE, beta, Bound::Lower);
+            return (NULL_MOVE, beta);
+        }
+    }
+
+    let mut best_move = NULL_MOVE;
 
     //move_sorter(&mut list, hist_move);
-    better_move_sorter(&mut list, &game.get_board(), hist_move);
-    //best_move_sorter(&mut list, game, map, hist_move, settings);
+    let prev_move = game.last_move();
+    let mut picker = MovePicker::new(&game.get_board(), hist_move, &ctx.ordering, ply, prev_move);
+    //best_move_sorter(&mut list, game, tt, hist_move, settings);
+
+    //Counted for the few replies extension without generating every move, the moves left after the multi pv exclusions at the root
+    //Without budget left the count can not extend anything and is skipped
+    let reply_count = if !extensions.can_extend_few_replies() {
+        FEW_REPLIES
+    }
+    else if ply == 0 {
+        game.get_legal_moves().iter().filter(|m| !ctx.excluded_root_moves.contains(m)).count()
+    }
+    else {
+        picker.get_evasion_count().unwrap_or_else(|| game.get_board().count_legal_moves(FEW_REPLIES))
+    };
+
+    let mut fm = NULL_MOVE;
+    let mut move_count = 0;
+    let lmp_move_count = settings.get_lmp_move_count(depth_left);
+    let mut searched_quiets = ArrayVec::<ChessMove, 200>::new();
+
+    let mut singular_move = NULL_MOVE;
+    if let Some(entry) = tt_entry {
+        let tt_score = entry.score.tt_to_search(ply);
+
+        //Only a fail high or exact result from a search not much shallower says the hash move is good enough to test
+        if settings.singular_extension && extensions.can_extend_singular() && use_tt && ply > 0 && depth_left >= settings.singular_min_depth 
+            && entry.bound != Bound::Upper && entry.depth + 3 >= depth_left && !tt_score.is_mate() && game.get_board().is_legal_move(hist_move)
+            && is_singular(game, hist_move, tt_score, depth_left, ply, extensions, table, tt, settings, ctx) {
+            singular_move = hist_move;
+        }
 
-    let fm = list[0];
+        if ctx.aborted {
+            return (NULL_MOVE, Score::DRAW);
+        }
+    }
+
+    while let Some(m) = picker.next(&ctx.ordering) {
+        if ply == 0 && ctx.excluded_root_moves.contains(&m) {
+            continue;
+        }
+
+        let i = move_count;
+        move_count += 1;
+
+        if i == 0 {
+            fm = m;
+        }
 
-    for i in  0..list.len() {
-        
-        let m = list[i];
         game.make_move(m);
+
+        let quiet = !(in_check || m.is_direct_capture() || m.is_en_passant() || m.is_promotion() || game.get_board().in_check());
+
+        if quiet && ply > 0 && (i >= lmp_move_count || (futile && i > 0)) {
+            game.undo_move();
+            continue;
+        }
  
-        let sub = if list.len() < 3 && extensions_left > 0 { 0 } else { 1 };
+        let (child_depth, child_extensions) = match extensions.extend(&game.get_board(), m, prev_move, singular_move, reply_count) {
+            Some(child_extensions) => (depth_left, child_extensions),
+            None => (depth_left - 1, extensions),
+        };
+
+        //Later moves only have to prove that they are worse than the best one so far
+        let can_zero_window = i > 0;
+        let zero_window = can_zero_window && settings.principal_variation_search;
+
+        let reduction = if can_zero_window && quiet { settings.get_lmr_reduction(depth_left, i).min(child_depth.saturating_sub(1)) } else { 0 };
 
-        let (line, mut value, gs) = alpha_beta_nega_max(game,  -beta, -alpha, depth_left - sub, max_depth, extensions_left -  (1 - sub), table, map, settings, stats);
+        let mut child = (NULL_MOVE, -alpha);
+        let mut search_full_depth = true;
+
+        //Late quiet moves rarely turn out best, so they get a cheaper search first
+        if reduction > 0 {
+            child = alpha_beta_nega_max(game, -alpha - NULL_WINDOW, -alpha, child_depth - reduction, ply + 1, child_extensions, table, tt, settings, ctx);
+            search_full_depth = -child.1 > alpha;
+        }
+
+        if search_full_depth && zero_window && !ctx.aborted {
+            child = alpha_beta_nega_max(game, -alpha - NULL_WINDOW, -alpha, child_depth, ply + 1, child_extensions, table, tt, settings, ctx);
+        }
+
+        if search_full_depth && !ctx.aborted && (!zero_window || (-child.1 > alpha && -child.1 < beta)) {
+            child = alpha_beta_nega_max(game,  -beta, -alpha, child_depth, ply + 1, child_extensions, table, tt, settings, ctx);
+        }
+
+        let value = -child.1;
         
         game.undo_move();
-        
-        value = -value;
+
+        if ctx.aborted {
+            return (NULL_MOVE, Score::DRAW);
+        }
         
         if value >= beta {
             //println!("Beta cutoff");
-            return (NULL_MOVE, beta, GameState::Undecided);
+            if MoveOrdering::is_quiet(m) {
+                ctx.ordering.add_cutoff(m, ply, depth_left, prev_move, &searched_quiets);
+            }
+
+            if use_tt {
+                tt.store(hash, depth_left, ply, m, beta, Bound::Lower);
+            }
+
+            return (NULL_MOVE, beta);
         }
         
+        if MoveOrdering::is_quiet(m) {
+            searched_quiets.push(m);
+        }
+
         //println!("Trying move: {} val {}, alpha {}", m.get_uci(), value, alpha);
         if value > alpha {
             alpha = value;
 
             best_move = m;
-            best_gs = gs;
+            ctx.pv.update(ply, m);
         }
     }    
 
+    //Every root move is excluded
+    if move_count == 0 {
+        return (NULL_MOVE, alpha);
+    }
+
     if best_move == fm {
-        stats.best_move_hits += 1;
+        ctx.stats.best_move_hits += 1;
     }
     else {
-        stats.not_best_move_hits += 1;
+        ctx.stats.not_best_move_hits += 1;
     }
 
-    if !best_move.is_null_move() {
-        if map.contains_key(&hash) {
-            let mut pair = map.get_mut(&hash).unwrap();
-            if depth_left > pair.0 {
-                *pair = (depth_left, best_move, alpha, best_gs);
-            }
-        }
-        else {
-            map.insert(hash, (depth_left, best_move, alpha, best_gs));
-        }
+    //Without a best move no move reached alpha
+    let bound = if best_move.is_null_move() { Bound::Upper } else { Bound::Exact };
+
+    if use_tt {
+        tt.store(hash, depth_left, ply, best_move, alpha, bound);
     }
     
     //println!("Returning: {}", best_move.get_uci());
-    return (best_move, alpha, best_gs);
+    return (best_move, alpha);
 }
 
-pub fn quiescence(game: &mut Game, mut alpha: f32, beta: f32, depth_left: u8, table: &EndgameTable, map: &HashMap<u64, (u8, ChessMove, f32, GameState)>, settings: &BBSettings) -> (ChessMove, f32, GameState) {
-    if game.get_game_state() != GameState::Undecided {
-        let pair = static_eval(game, &settings.eval_factors, false);
-        return (chess_move::NULL_MOVE, pair.0, pair.1);
+//The hash move is singular if every other move fails low against a window somewhat below its stored score
+fn is_singular(game: &mut Game, hash_move: ChessMove, tt_score: Score, depth_left: u8, ply: u8, extensions: Extensions, table: &EndgameTable, tt: &TranspositionTable, settings: &BBSettings, ctx: &mut SearchContext) -> bool {
+    let singular_beta = tt_score - (settings.singular_margin * 100.0 * depth_left as f32) as i32;
+    let child_depth = (depth_left / 2).saturating_sub(1);
+
+    for m in game.get_legal_moves() {
+        if m == hash_move {
+            continue;
+        }
+
+        game.make_move(m);
+
+        let value = -alpha_beta_nega_max(game, -singular_beta, -singular_beta + NULL_WINDOW, child_depth, ply + 1, extensions, table, tt, settings, ctx).1;
+
+        game.undo_move();
+
+        if ctx.aborted || value >= singular_beta {
+            return false;
+        }
+    }
+
+    return true;
+}
+
+fn try_null_move(game: &mut Game, beta: Score, depth_left: u8, ply: u8, settings: &BBSettings) -> bool {
+    //Passing cannot prove a mate
+    if !settings.null_move_pruning || ply == 0 || depth_left < settings.null_move_pruning_depth || beta.is_mate() {
+        return false;
+    }
+
+    //Two null moves in a row would just search the same position with less depth
+    if game.last_move_is_null_move() {
+        return false;
+    }
+
+    let board = game.get_board();
+
+    //Passing is illegal in check and zugzwang is common when only pawns are left
+    if board.in_check() || !board.has_non_pawn_material(board.is_whites_turn()) {
+        return false;
+    }
+
+    return Score::from_pawns(static_eval(game, &settings.eval_factors, false).0 + settings.null_move_pruning_margin) >= beta;
+}
+
+pub fn quiescence(game: &mut Game, mut alpha: Score, beta: Score, depth_left: u8, ply: u8, table: &EndgameTable, tt: &TranspositionTable, settings: &BBSettings, ctx: &mut SearchContext) -> (ChessMove, Score) {
+    ctx.stats.nodes += 1;
+
+    if ctx.should_abort() {
+        return (NULL_MOVE, Score::DRAW);
+    }
+
+    if let Some(score) = get_terminal_score(game, ply) {
+        return (chess_move::NULL_MOVE, score);
     }
     
     if settings.end_game_table {
-        let pair = get_relative_endgame_eval(&game.get_board(), table);
-        if pair.1 != GameState::Undecided {
-            return (chess_move::NULL_MOVE, pair.0, pair.1);
+        if let Some(score) = get_relative_endgame_eval(&game.get_board(), table, ply) {
+            return (chess_move::NULL_MOVE, score);
         }
     }
     
     if game.get_board().in_check() {
-        return check_avoid_search(game, alpha, beta, depth_left, table, map, settings);
+        return check_avoid_search(game, alpha, beta, depth_left, ply, table, tt, settings, ctx);
     }
     
-    let (stand_pat, sp_gs) = static_eval(game, &settings.eval_factors, false);
+    let stand_pat = evaluate(game, settings);
 
     if stand_pat >= beta {
-        return (NULL_MOVE, beta, GameState::Undecided);
+        return (NULL_MOVE, beta);
     }
     
     //only for quiescence search
@@ -412,53 +970,83 @@ pub fn quiescence(game: &mut Game, mut alpha: f32, beta: f32, depth_left: u8, ta
     
     if depth_left <= 0 {
         //println!("Could not finish quiescence search");
-        return (NULL_MOVE, stand_pat, sp_gs);
+        return (NULL_MOVE, stand_pat);
     }
     
+    //Every stored entry was searched at least as deep as the rest of this quiescence search
     let hash = game.get_board().get_zoberist_hash();
     let mut hist_move = chess_move::NULL_MOVE;
-    if map.contains_key(&hash) {
-        let (hist_depth_left, m , hist_value, hist_gs) = map[&hash];
+    if let Some(entry) = tt.probe(hash) {
+        hist_move = entry.best_move.unpack(&game.get_board());
 
-        hist_move = m;
-        if hist_depth_left >= depth_left {
-            return (hist_move, hist_value, hist_gs);
+        if let Some(score) = entry.get_cutoff_score(alpha, beta, ply) {
+            return (hist_move, score);
         }
     }
 
     let mut best_move = NULL_MOVE;
-    let mut best_gs = GameState::Undecided;
 
-    let mut list = game.get_legal_moves();
+    let board = game.get_board();
+    //Quiet checks are only tried right after the main search, later plies would blow up the tree
+    let search_checks = settings.qs_checks && depth_left == settings.max_quiescence_depth;
+
+    let mut list = board.get_legal_captures();
+
+    if search_checks {
+        list.extend(board.get_legal_quiets());
+    }
 
     move_sorter(&mut list, hist_move);
+    let delta = Score::from_pawns(settings.qs_delta_margin).get_value();
 
     for m in  list {
-        if !(m.is_direct_capture() || m.is_en_passant() || m.is_promotion()) {
+        let tactical = m.is_capture() || m.is_promotion();
+
+        if !tactical && !search_checks {
             continue;
         }
 
+        if tactical && !m.is_promotion() {
+            let captured = if m.is_en_passant() { bit_board::SEE_VALUES[0] } else { bit_board::SEE_VALUES[PieceType::from_cpt(m.capture_piece_type) as usize] };
+
+            //Even winning the piece for free stays below alpha
+            if settings.qs_delta_pruning && stand_pat + captured + delta <= alpha {
+                continue;
+            }
+
+            //Losing captures rarely change the outcome of the quiescence search
+            if settings.qs_see_pruning && board.see(m) < 0 {
+                continue;
+            }
+        }
+
         game.make_move(m);
 
-        let (line, mut value, gs) = quiescence(game,  -beta, -alpha, depth_left - 1, table, map, settings);
+        if !tactical && !game.get_board().in_check() {
+            game.undo_move();
+            continue;
+        }
+
+        let value = -quiescence(game,  -beta, -alpha, depth_left - 1, ply + 1, table, tt, settings, ctx).1;
 
         game.undo_move();
 
-        value = -value;
+        if ctx.aborted {
+            return (NULL_MOVE, Score::DRAW);
+        }
 
         if value >= beta {
-            return (NULL_MOVE, beta, GameState::Undecided);
+            return (NULL_MOVE, beta);
         }
 
         if value > alpha {
             alpha = value;
 
             best_move = m;
-            best_gs = gs;
         }
     }    
 
-    return (best_move, alpha, best_gs);
+    return (best_move, alpha);
 }
 
 pub fn is_quiet_pos(board: &mut BitBoard) -> bool {
@@ -480,9 +1068,8 @@ pub fn is_quiet_pos(board: &mut BitBoard) -> bool {
     return true;
 }
 
-pub fn check_avoid_search(game: &mut Game, mut alpha: f32, beta: f32, depth_left: u8, table: &EndgameTable, map: &HashMap<u64, (u8, ChessMove, f32, GameState)>, settings: &BBSettings) -> (ChessMove, f32, GameState) {
+pub fn check_avoid_search(game: &mut Game, mut alpha: Score, beta: Score, depth_left: u8, ply: u8, table: &EndgameTable, tt: &TranspositionTable, settings: &BBSettings, ctx: &mut SearchContext) -> (ChessMove, Score) {
     let mut best_move = NULL_MOVE;
-    let mut best_gs = GameState::Undecided;
 
     let mut list = game.get_legal_moves();
 
@@ -491,23 +1078,56 @@ pub fn check_avoid_search(game: &mut Game, mut alpha: f32, beta: f32, depth_left
     for m in  list {
         game.make_move(m);
 
-        let (line, mut value, gs) = quiescence(game,  -beta, -alpha, depth_left - if depth_left > 0 { 1 } else { 0 } , table, map, settings);
+        let value = -quiescence(game,  -beta, -alpha, depth_left - if depth_left > 0 { 1 } else { 0 } , ply + 1, table, tt, settings, ctx).1;
 
         game.undo_move();
 
-        value = -value;
+        if ctx.aborted {
+            return (NULL_MOVE, Score::DRAW);
+        }
 
         if value >= beta {
-            return (NULL_MOVE, beta, GameState::Undecided);
+            return (NULL_MOVE, beta);
         }
 
         if value > alpha {
             alpha = value;
 
-            best_move = line;
-            best_gs = gs;
+            best_move = m;
         }
     }    
 
-    return (best_move, alpha, best_gs);
-}
\ No newline at end of file
+    return (best_move, alpha);
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transposition_table;
+
+    //Best move and node count of a node limited deterministic search
+    fn run_node_limited(fen: &str, nodes: u64) -> (ChessMove, u64) {
+        let table = EndgameTable::load(0);
+        let settings = BBSettings { deterministic: true, max_depth: MAX_SEARCH_DEPTH, ..bb_settings::STANDARD_SETTINGS };
+        let time_control = TimeControl { nodes: Some(nodes), ..TimeControl::none() };
+        let mut tt = TranspositionTable::new(transposition_table::DEFAULT_SIZE_MB);
+        let signals = SearchSignals::new();
+        let mut last_nodes = 0;
+
+        let time_manager = TimeManager::new(&time_control, true);
+        let line = iterative_deepening(&mut Game::from_fen(fen), &table, &settings, &mut tt, time_manager, &signals, &mut |info| last_nodes = info.nodes);
+
+        return (line.chess_move, last_nodes);
+    }
+
+    #[test]
+    fn node_limited_search_is_reproducible() {
+        const FEN: &str = "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3";
+
+        let first = run_node_limited(FEN, 3000);
+        let second = run_node_limited(FEN, 3000);
+
+        assert!(first.0 == second.0);
+        assert_eq!(first.1, second.1);
+        assert!(first.1 <= 3000);
+    }
+}
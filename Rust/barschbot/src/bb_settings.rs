@@ -4,11 +4,59 @@ use crate::evaluation::EvalAttributes;
 pub struct BBSettings {
     pub max_depth: u8,
     pub max_quiescence_depth: u8,
+    //Captures that cannot lift the stand pat above alpha even with this many extra pawns are skipped
+    pub qs_delta_pruning: bool,
+    pub qs_delta_margin: f32,
+    //Skips captures that lose material according to the static exchange evaluation
+    pub qs_see_pruning: bool,
+    //Also searches quiet moves that give check in the first ply of the quiescence search, off by default because every quiet move is tried
+    pub qs_checks: bool,
     pub end_game_table: bool,
     pub null_move_pruning: bool,
     pub null_move_pruning_margin: f32,
     pub null_move_pruning_depth: u8,
+    pub principal_variation_search: bool,
+    pub aspiration_windows: bool,
+    pub aspiration_window_size: f32,
+    pub late_move_reductions: bool,
+    pub lmr_min_depth: u8,
+    pub lmr_min_move_index: u8,
+    pub lmr_base: f32,
+    pub lmr_divisor: f32,
+    pub late_move_pruning: bool,
+    pub lmp_max_depth: u8,
+    pub lmp_base: f32,
+    pub lmp_factor: f32,
+    //Margins in pawns per ply of depth left, compared against the static eval
+    pub futility_pruning: bool,
+    pub futility_max_depth: u8,
+    pub futility_margin: f32,
+    pub reverse_futility_pruning: bool,
+    pub rfp_max_depth: u8,
+    pub rfp_margin: f32,
+    pub razoring: bool,
+    pub razoring_max_depth: u8,
+    pub razoring_margin: f32,
+    //Budget of the extension for nodes with less than three legal moves
     pub max_extensions: u8,
+    //Every extension kind has a switch and its own budget of plies per path
+    pub check_extension: bool,
+    pub check_extension_budget: u8,
+    pub recapture_extension: bool,
+    pub recapture_extension_budget: u8,
+    pub passed_pawn_extension: bool,
+    pub passed_pawn_extension_budget: u8,
+    pub singular_extension: bool,
+    pub singular_extension_budget: u8,
+    pub singular_min_depth: u8,
+    //Pawns per ply of depth the other moves have to stay below the hash move
+    pub singular_margin: f32,
+    //Lazy SMP search threads including the main thread
+    pub threads: u8,
+    //Number of best root moves reported with their own score and line
+    pub multi_pv: u8,
+    //Same position and settings give the same result, ignores the clock, runs one thread and starts with an empty transposition table
+    pub deterministic: bool,
     pub eval_factors: EvalFactors,
     pub min_search_time: u64
 }
@@ -56,15 +104,143 @@ pub const ALL_NAMES: [FactorName; 33] = [
     FactorName::UnsafeCheck,
 ];
 
+//Search settings the tuner can optimize next to the eval factors
+#[derive(Debug, Copy, Clone)]
+pub enum SearchParameter {
+    LmrMinDepth, LmrMinMoveIndex, LmrBase, LmrDivisor,
+    LmpMaxDepth, LmpBase, LmpFactor,
+    FutilityMargin, RfpMargin, RazoringMargin,
+}
+
+pub const ALL_SEARCH_PARAMETERS: [SearchParameter; 10] = [
+    SearchParameter::LmrMinDepth, SearchParameter::LmrMinMoveIndex, SearchParameter::LmrBase, SearchParameter::LmrDivisor,
+    SearchParameter::LmpMaxDepth, SearchParameter::LmpBase, SearchParameter::LmpFactor,
+    SearchParameter::FutilityMargin, SearchParameter::RfpMargin, SearchParameter::RazoringMargin,
+];
+
+impl SearchParameter {
+    pub fn is_integer(&self) -> bool {
+        return match *self {
+            SearchParameter::LmrMinDepth | SearchParameter::LmrMinMoveIndex | SearchParameter::LmpMaxDepth => true,
+            _ => false,
+        };
+    }
+}
+
+impl BBSettings {
+    pub fn get_search_parameter(&self, parameter: SearchParameter) -> f32 {
+        return match parameter {
+            SearchParameter::LmrMinDepth => self.lmr_min_depth as f32,
+            SearchParameter::LmrMinMoveIndex => self.lmr_min_move_index as f32,
+            SearchParameter::LmrBase => self.lmr_base,
+            SearchParameter::LmrDivisor => self.lmr_divisor,
+            SearchParameter::LmpMaxDepth => self.lmp_max_depth as f32,
+            SearchParameter::LmpBase => self.lmp_base,
+            SearchParameter::LmpFactor => self.lmp_factor,
+            SearchParameter::FutilityMargin => self.futility_margin,
+            SearchParameter::RfpMargin => self.rfp_margin,
+            SearchParameter::RazoringMargin => self.razoring_margin,
+        };
+    }
+
+    pub fn set_search_parameter(&mut self, parameter: SearchParameter, value: f32) {
+        let int_value = value.round().clamp(1.0, u8::MAX as f32) as u8;
+
+        match parameter {
+            SearchParameter::LmrMinDepth => self.lmr_min_depth = int_value,
+            SearchParameter::LmrMinMoveIndex => self.lmr_min_move_index = int_value,
+            SearchParameter::LmrBase => self.lmr_base = value,
+            //Zero would divide the reductions into infinity
+            SearchParameter::LmrDivisor => self.lmr_divisor = value.max(0.1),
+            SearchParameter::LmpMaxDepth => self.lmp_max_depth = int_value,
+            SearchParameter::LmpBase => self.lmp_base = value,
+            SearchParameter::LmpFactor => self.lmp_factor = value,
+            SearchParameter::FutilityMargin => self.futility_margin = value.max(0.0),
+            SearchParameter::RfpMargin => self.rfp_margin = value.max(0.0),
+            SearchParameter::RazoringMargin => self.razoring_margin = value.max(0.0),
+        }
+    }
+
+    //Depth taken away from a late quiet move, the reduction table as a formula so it stays tunable
+    pub fn get_lmr_reduction(&self, depth_left: u8, move_index: usize) -> u8 {
+        if !self.late_move_reductions || depth_left < self.lmr_min_depth || move_index < self.lmr_min_move_index as usize {
+            return 0;
+        }
+
+        let reduction = self.lmr_base + (depth_left as f32).ln() * (move_index as f32).ln() / self.lmr_divisor;
+
+        return reduction.max(0.0) as u8;
+    }
+
+    //Quiet moves after this many are skipped at shallow depth
+    pub fn get_lmp_move_count(&self, depth_left: u8) -> usize {
+        if !self.late_move_pruning || depth_left > self.lmp_max_depth {
+            return usize::MAX;
+        }
+
+        return (self.lmp_base + self.lmp_factor * (depth_left as f32 * depth_left as f32)).max(1.0) as usize;
+    }
+
+    //Static eval margin in centipawns for pruning with margin pawns per ply at the given depth
+    pub fn get_depth_margin(margin: f32, depth_left: u8) -> i32 {
+        return (margin * 100.0 * depth_left as f32) as i32;
+    }
+
+    pub fn print_search_parameters(&self) {
+        println!("Search parameters: ");
+        for p in ALL_SEARCH_PARAMETERS {
+            println!("\t{:?} -> {}", p, self.get_search_parameter(p));
+        }
+    }
+}
+
 pub const STANDARD_SETTINGS: BBSettings = BBSettings { 
     max_depth: 4, 
     max_quiescence_depth: 3, 
+    qs_delta_pruning: true,
+    qs_delta_margin: 2.0,
+    qs_see_pruning: true,
+    qs_checks: false,
     max_extensions: 2, 
+    check_extension: true,
+    check_extension_budget: 4,
+    recapture_extension: false,
+    recapture_extension_budget: 2,
+    passed_pawn_extension: true,
+    passed_pawn_extension_budget: 2,
+    singular_extension: true,
+    singular_extension_budget: 2,
+    singular_min_depth: 6,
+    singular_margin: 0.02,
     end_game_table: true, 
     null_move_pruning: true,
     null_move_pruning_margin: 0.3,
     null_move_pruning_depth: 3, 
+    principal_variation_search: true,
+    aspiration_windows: true,
+    aspiration_window_size: 0.5,
+    late_move_reductions: true,
+    lmr_min_depth: 3,
+    lmr_min_move_index: 3,
+    lmr_base: 0.75,
+    lmr_divisor: 2.25,
+    late_move_pruning: false,
+    lmp_max_depth: 3,
+    lmp_base: 3.0,
+    lmp_factor: 1.0,
+    futility_pruning: true,
+    futility_max_depth: 2,
+    futility_margin: 1.0,
+    reverse_futility_pruning: true,
+    rfp_max_depth: 4,
+    rfp_margin: 0.8,
+    razoring: true,
+    razoring_max_depth: 2,
+    razoring_margin: 2.5,
     min_search_time: 0, 
+    threads: 1,
+    multi_pv: 1,
+    deterministic: false,
     eval_factors: STANDARD_EVAL_FACTORS };
 
 #[derive(Clone)]
@@ -0,0 +1,54 @@
+use std::time::Instant;
+
+use crate::{barsch_bot::{self, SearchSignals}, bb_settings::{self, BBSettings}, endgame_table::EndgameTable, game::Game, time_manager::TimeManager, transposition_table::{self, TranspositionTable}};
+
+pub const DEFAULT_BENCH_DEPTH: u8 = 7;
+
+//Openings, middlegames and endgames with tactics, checks and promotions
+const BENCH_FENS: [&str; 16] = [
+    "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+    "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3",
+    "rnbqkb1r/pp1p1ppp/4pn2/2p5/2PP4/2N5/PP2PPPP/R1BQKBNR w KQkq - 0 4",
+    "r1bqk2r/pppp1ppp/2n2n2/2b1p3/2B1P3/2N2N2/PPPP1PPP/R1BQK2R w KQkq - 6 5",
+    "r2q1rk1/ppp2ppp/2np1n2/2b1p1B1/2B1P1b1/2NP1N2/PPP2PPP/R2Q1RK1 w - - 0 8",
+    "r1bq1rk1/pp2bppp/2n1pn2/3p4/2PP4/2N1PN2/PP1B1PPP/R2QKB1R w KQ - 0 8",
+    "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+    "2rq1rk1/pb1nbppp/1p2pn2/2pp4/2PP4/1PN1PN2/PB2BPPP/2RQ1RK1 w - - 0 11",
+    "r1b2rk1/2q1bppp/p2ppn2/1p6/3BPP2/2N2B2/PPPQ2PP/2KR3R w - - 0 13",
+    "3r2k1/pp3ppp/2p1b3/4P3/2B5/2P5/PP3PPP/4R1K1 w - - 0 20",
+    "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+    "8/8/4k3/3p4/3P4/4K3/8/8 w - - 0 1",
+    "6k1/5ppp/8/8/8/8/5PPP/3R2K1 w - - 0 1",
+    "8/5P2/8/8/8/2k5/8/4K3 w - - 0 1",
+    "r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10",
+    "4rrk1/pp1n3p/3q2pQ/2p1pb2/2PP4/2P3N1/P2B2PP/4RRK1 b - - 7 19",
+];
+
+//Searches every bench position to a fixed depth, the total node count is a signature of the search behaviour
+pub fn run_bench(depth: u8) {
+    let table = EndgameTable::load(0);
+    let settings = BBSettings { max_depth: depth, deterministic: true, end_game_table: false, threads: 1, ..bb_settings::STANDARD_SETTINGS };
+    let mut tt = TranspositionTable::new(transposition_table::DEFAULT_SIZE_MB);
+    let signals = SearchSignals::new();
+
+    let mut total_nodes = 0;
+    let start = Instant::now();
+
+    for (i, fen) in BENCH_FENS.iter().enumerate() {
+        let mut game = Game::from_fen(fen);
+        let mut nodes = 0;
+
+        let line = barsch_bot::iterative_deepening(&mut game, &table, &settings, &mut tt, TimeManager::infinite(), &signals, &mut |info| nodes = info.nodes);
+
+        println!("Position {:>2}/{}: {:>10} nodes, best move {}", i + 1, BENCH_FENS.len(), nodes, line.chess_move.get_uci());
+
+        total_nodes += nodes;
+    }
+
+    let time_ms = start.elapsed().as_millis().max(1);
+
+    println!("Depth: {}", depth);
+    println!("Time: {} ms", time_ms);
+    println!("Nodes: {}", total_nodes);
+    println!("NPS: {}", total_nodes as u128 * 1000 / time_ms);
+}
@@ -1,22 +1,31 @@
 use core::panic;
-use std::char;
+use std::{char, cmp};
 use arrayvec::ArrayVec;
 
-use crate::{bitboard_helper::{self, toggle_bit}, chess_move::ChessMove, square::Square, colored_piece_type::ColoredPieceType, piece_type::PieceType, endgame_table::BoardState, zoberist_hash::ZoberistHash64};
+use crate::{bitboard_helper::{self, toggle_bit}, magic_bitboard, chess_move::ChessMove, square::Square, colored_piece_type::ColoredPieceType, piece_type::PieceType, endgame_table::BoardState, zoberist_hash::{self, ZoberistHash64}};
 
 
+//Centipawn values used by the static exchange evaluation, indexed by PieceType
+pub const SEE_VALUES: [i32; 7] = [100, 300, 300, 500, 900, 20000, 0];
 
 //const DEBUG: bool = std::cfg!(debug_assertions);
 
+//Kind of moves the legal move generator produces
+//Promotions count as captures, so the quiet moves are exactly those MoveOrdering::is_quiet accepts
+#[derive(Clone, Copy, PartialEq)]
+enum MoveGenType {
+    All, Captures, Quiets
+}
+
 
 #[derive(Clone, Copy)]
 pub struct BitBoard {
     whites_turn: bool,
 
-    pub white_king_castle: bool,
-    pub black_king_castle: bool,
-    pub white_queen_castle: bool,
-    pub black_queen_castle: bool,
+    white_king_castle: bool,
+    black_king_castle: bool,
+    white_queen_castle: bool,
+    black_queen_castle: bool,
 
     en_passant_square: Square,
 
@@ -28,14 +37,18 @@ pub struct BitBoard {
     diagonal_sliders: u64,
     kings: u64,
 
-    pub type_field: [ColoredPieceType; 64]
+    pub type_field: [ColoredPieceType; 64],
+
+    //Updated incrementally by every piece placement and state change
+    zoberist_hash: u64,
 }
 
 impl BitBoard {
     pub fn empty() -> Self {
         return BitBoard { whites_turn: true, white_queen_castle: false, white_king_castle: false, black_queen_castle: false, black_king_castle: false,
             en_passant_square: Square::None, 
-            white_pieces: 0, black_pieces: 0, pawns: 0, knights: 0, orthogonal_sliders: 0, diagonal_sliders: 0, kings: 0, type_field: [ColoredPieceType::None; 64] };
+            white_pieces: 0, black_pieces: 0, pawns: 0, knights: 0, orthogonal_sliders: 0, diagonal_sliders: 0, kings: 0, type_field: [ColoredPieceType::None; 64],
+            zoberist_hash: ZoberistHash64::get_state_hash(true, Square::None, false, false, false, false) };
     }
 
     pub fn start_position() -> Self {
@@ -47,7 +60,9 @@ impl BitBoard {
     }
 
     pub fn set_whites_turn(&mut self, whites_turn: bool) {
+        self.zoberist_hash ^= self.get_state_hash();
         self.whites_turn = whites_turn;
+        self.zoberist_hash ^= self.get_state_hash();
     }
 
     pub fn from_type_field(type_field: [ColoredPieceType; 64]) -> Self {
@@ -73,6 +88,7 @@ impl BitBoard {
 
         board.set_whites_turn(bs.whites_turn);
         board.en_passant_square = bs.ep_square;
+        board.zoberist_hash = board.calculate_zoberist_hash();
 
         return board;
     }
@@ -258,6 +274,8 @@ impl BitBoard {
             board.en_passant_square = Square::from_str(parts[3]);
         }
 
+        board.zoberist_hash = board.calculate_zoberist_hash();
+
         //println!("Loaded FEN {}", fen);
 
         return board;
@@ -335,10 +353,49 @@ impl BitBoard {
     }
 
     pub fn get_zoberist_hash(&self) -> u64 {
+        return self.zoberist_hash;
+    }
+
+    //Full recalculation from all 64 squares, only needed after the state was written directly
+    fn calculate_zoberist_hash(&self) -> u64 {
         return ZoberistHash64::calculate_hash(&self.type_field, self.whites_turn, self.en_passant_square, 
             self.white_queen_castle, self.white_king_castle, self.black_queen_castle, self.black_king_castle);
     }
 
+    fn get_state_hash(&self) -> u64 {
+        let ep_square = if self.can_capture_en_passant() { self.en_passant_square } else { Square::None };
+
+        return ZoberistHash64::get_state_hash(self.whites_turn, ep_square, 
+            self.white_queen_castle, self.white_king_castle, self.black_queen_castle, self.black_king_castle);
+    }
+
+    //Only a pawn next to the double moved one is needed, pins are ignored like in the Polyglot format
+    fn can_capture_en_passant(&self) -> bool {
+        if self.en_passant_square == Square::None {
+            return false;
+        }
+
+        let (color_mask, attack_mask) = if self.whites_turn { 
+            (self.white_pieces, bitboard_helper::BLACK_PAWN_ATTACKS[self.en_passant_square as usize]) 
+        } 
+        else { 
+            (self.black_pieces, bitboard_helper::WHITE_PAWN_ATTACKS[self.en_passant_square as usize]) 
+        };
+
+        return self.pawns & color_mask & attack_mask != 0;
+    }
+
+    //Hash the endgame table files were created with
+    pub fn get_legacy_hash(&self) -> u64 {
+        return ZoberistHash64::calculate_legacy_hash(&self.type_field, self.whites_turn, self.en_passant_square, 
+            self.white_queen_castle, self.white_king_castle, self.black_queen_castle, self.black_king_castle);
+    }
+
+    pub fn get_polyglot_hash(&self) -> u64 {
+        return zoberist_hash::calculate_polyglot_hash(&self.type_field, self.whites_turn, self.en_passant_square, 
+            self.white_queen_castle, self.white_king_castle, self.black_queen_castle, self.black_king_castle);
+    }
+
     fn toggle_piece_bitboards(&mut self, colored_piece_type: ColoredPieceType, square: Square) {
         match PieceType::from_cpt(colored_piece_type) {
             PieceType::Pawn     => toggle_bit(&mut self.pawns, square),
@@ -362,12 +419,14 @@ impl BitBoard {
     fn place_piece(&mut self, colored_piece_type: ColoredPieceType, square: Square) {
         self.toggle_piece_bitboards(colored_piece_type, square);
         self.type_field[square as usize] = colored_piece_type;
+        self.zoberist_hash ^= ZoberistHash64::get_piece_hash(square, colored_piece_type);
     }
 
     fn remove_piece(&mut self, square: Square) {
         let cpt = self.type_field[square as usize];
         self.toggle_piece_bitboards(cpt, square);
         self.type_field[square as usize] = ColoredPieceType::None;
+        self.zoberist_hash ^= ZoberistHash64::get_piece_hash(square, cpt);
     }
 
     fn move_piece(&mut self, start_square: Square, target_square: Square) {
@@ -399,29 +458,8 @@ impl BitBoard {
        
         let all_pieces = self.white_pieces | self.black_pieces;
         
-        let diagonal_attackers = self.diagonal_sliders & color_mask & bitboard_helper::DIAGONAL_ATTACKS[target_square as usize];
-        if sliders_have_attack(target_square, diagonal_attackers, all_pieces) {
-            return true;
-        }
-
-        let orthogonal_attackers = self.orthogonal_sliders & color_mask & bitboard_helper::ORTHOGONAL_ATTACKS[target_square as usize];
-        if sliders_have_attack(target_square, orthogonal_attackers, all_pieces) {
-            return true;
-        }
-        
-        fn sliders_have_attack(target_square: Square, mut sliders: u64, blockers: u64) -> bool {
-            for index in bitboard_helper::iterate_set_bits(sliders) {
-                let in_between = bitboard_helper::get_in_between(target_square, Square::from_u8(index as u8));
-
-                if blockers & in_between == 0 {
-                    return true;
-                }
-            }
-
-            return false;
-        }
-
-        return false;
+        return magic_bitboard::bishop_attacks(target_square, all_pieces) & self.diagonal_sliders & color_mask != 0
+            || magic_bitboard::rook_attacks(target_square, all_pieces) & self.orthogonal_sliders & color_mask != 0;
     }
 
     pub fn get_piece_count(&self, colored_piece_type: ColoredPieceType) -> u32 {
@@ -440,6 +478,13 @@ impl BitBoard {
         return bits.count_ones();
     }
 
+    //Knights, bishops, rooks and queens of one side
+    pub fn has_non_pawn_material(&self, white: bool) -> bool {
+        let color_mask = if white { self.white_pieces } else { self.black_pieces };
+
+        return color_mask & (self.knights | self.orthogonal_sliders | self.diagonal_sliders) != 0;
+    }
+
     pub fn get_piece_bitboard(&self, colored_piece_type: ColoredPieceType) -> u64 {
         let color_mask = if colored_piece_type.is_white() { self.white_pieces } else { self.black_pieces };
 
@@ -493,23 +538,11 @@ impl BitBoard {
 
         let all_mask = self.white_pieces | self.black_pieces;
         if colored_piece_type.is_diagonal_slider() {
-            for index in bitboard_helper::iterate_set_bits(bitboard_helper::DIAGONAL_ATTACKS[square as usize] & opponent_mask) {
-                let in_between = bitboard_helper::get_in_between(square, Square::from_u8(index as u8));
-
-                if in_between & all_mask == 0 {
-                    res |= 1_u64 << index;
-                }
-            }
+            res |= magic_bitboard::bishop_attacks(square, all_mask) & opponent_mask;
         }
 
         if colored_piece_type.is_orthogonal_slider() {
-            for index in bitboard_helper::iterate_set_bits(bitboard_helper::ORTHOGONAL_ATTACKS[square as usize] & opponent_mask) {
-                let in_between = bitboard_helper::get_in_between(square, Square::from_u8(index as u8));
-
-                if in_between & all_mask == 0 {
-                    res |= 1_u64 << index;
-                }
-            }
+            res |= magic_bitboard::rook_attacks(square, all_mask) & opponent_mask;
         }
 
         let mut list = ArrayVec::new();
@@ -540,23 +573,11 @@ impl BitBoard {
 
         let all_mask = self.white_pieces | self.black_pieces;
         if piece_type.is_diagonal_slider() {
-            for index in bitboard_helper::iterate_set_bits(bitboard_helper::DIAGONAL_ATTACKS[square as usize]) {
-                let in_between = bitboard_helper::get_in_between(square, Square::from_u8(index as u8));
-
-                if in_between & all_mask == 0 {
-                    res |= 1_u64 << index;
-                }
-            }
+            res |= magic_bitboard::bishop_attacks(square, all_mask);
         }
 
         if piece_type.is_orthogonal_slider() {
-            for index in bitboard_helper::iterate_set_bits(bitboard_helper::ORTHOGONAL_ATTACKS[square as usize]) {
-                let in_between = bitboard_helper::get_in_between(square, Square::from_u8(index as u8));
-
-                if in_between & all_mask == 0 {
-                    res |= 1_u64 << index;
-                }
-            }
+            res |= magic_bitboard::rook_attacks(square, all_mask);
         }
 
         return res.count_ones() as u8;
@@ -575,29 +596,8 @@ impl BitBoard {
        
         let all_pieces = (self.white_pieces | self.black_pieces) & !(1_u64 << self.get_king_square(!white) as u8);
         
-        let diagonal_attackers = self.diagonal_sliders & color_mask & bitboard_helper::DIAGONAL_ATTACKS[target_square as usize];
-        if sliders_have_attack(target_square, diagonal_attackers, all_pieces) {
-            return true;
-        }
-
-        let orthogonal_attackers = self.orthogonal_sliders & color_mask & bitboard_helper::ORTHOGONAL_ATTACKS[target_square as usize];
-        if sliders_have_attack(target_square, orthogonal_attackers, all_pieces) {
-            return true;
-        }
-        
-        fn sliders_have_attack(target_square: Square, mut sliders: u64, blockers: u64) -> bool {
-            for index in bitboard_helper::iterate_set_bits(sliders) {
-                let in_between = bitboard_helper::get_in_between(target_square, Square::from_u8(index as u8));
-
-                if blockers & in_between == 0 {
-                    return true;
-                }
-            }
-
-            return false;
-        }
-
-        return false;
+        return magic_bitboard::bishop_attacks(target_square, all_pieces) & self.diagonal_sliders & color_mask != 0
+            || magic_bitboard::rook_attacks(target_square, all_pieces) & self.orthogonal_sliders & color_mask != 0;
     }
 
     pub fn get_piece_type(&self, target_square: Square) -> ColoredPieceType {
@@ -620,25 +620,113 @@ impl BitBoard {
         
         let all_pieces = self.white_pieces | self.black_pieces;
         
-        let diagonal_attackers = self.diagonal_sliders & color_mask & bitboard_helper::DIAGONAL_ATTACKS[target_square as usize];
+        let slider_attackers = (magic_bitboard::bishop_attacks(target_square, all_pieces) & self.diagonal_sliders
+            | magic_bitboard::rook_attacks(target_square, all_pieces) & self.orthogonal_sliders) & color_mask;
 
-        add_slide_attacks(target_square, diagonal_attackers, all_pieces, &mut list); 
+        for index in bitboard_helper::iterate_set_bits(slider_attackers) {
+            list.push(Square::from_u8(index as u8));
+        }
 
-        let orthogonal_attackers = self.orthogonal_sliders & color_mask & bitboard_helper::ORTHOGONAL_ATTACKS[target_square as usize];
+        return list;
+    }
 
-        add_slide_attacks(target_square, orthogonal_attackers, all_pieces, &mut list); 
+    //Attackers of both colors, sliders only see through squares that are empty in occupied
+    fn get_attackers_to(&self, target_square: Square, occupied: u64) -> u64 {
+        let index = target_square as usize;
 
-        fn add_slide_attacks(target_square: Square, mut sliders: u64, blockers: u64, list: &mut ArrayVec<Square, 16>) {
-            for index in bitboard_helper::iterate_set_bits(sliders) {
-                let in_between = bitboard_helper::get_in_between(target_square, Square::from_u8(index as u8));
+        let mut attackers = self.kings & bitboard_helper::KING_ATTACKS[index]
+            | self.knights & bitboard_helper::KNIGHT_ATTACKS[index]
+            | self.pawns & self.white_pieces & bitboard_helper::BLACK_PAWN_ATTACKS[index]
+            | self.pawns & self.black_pieces & bitboard_helper::WHITE_PAWN_ATTACKS[index];
 
-                if blockers & in_between == 0 {
-                    list.push(Square::from_u8(index as u8));
-                }
+        attackers |= magic_bitboard::bishop_attacks(target_square, occupied) & self.diagonal_sliders
+            | magic_bitboard::rook_attacks(target_square, occupied) & self.orthogonal_sliders;
+
+        return attackers & occupied;
+    }
+
+    //Cheapest piece of the given attackers as (bitboard with only that piece, centipawn value)
+    fn get_least_valuable_attacker(&self, attackers: u64) -> (u64, i32) {
+        let by_value = [
+            (self.pawns, PieceType::Pawn),
+            (self.knights, PieceType::Knight),
+            (self.diagonal_sliders & !self.orthogonal_sliders, PieceType::Bishop),
+            (self.orthogonal_sliders & !self.diagonal_sliders, PieceType::Rook),
+            (self.diagonal_sliders & self.orthogonal_sliders, PieceType::Queen),
+            (self.kings, PieceType::King),
+        ];
+
+        for (pieces, piece_type) in by_value {
+            let candidates = attackers & pieces;
+
+            if candidates != 0 {
+                return (candidates & candidates.wrapping_neg(), SEE_VALUES[piece_type as usize]);
             }
         }
 
-        return list;
+        return (0, 0);
+    }
+
+    //Material won by the side making the move in centipawns, assuming both sides keep recapturing on 
+    //the target square with their cheapest piece as long as it pays off. X-ray attackers join in once the pieces in front of them are gone.
+    pub fn see(&self, m: ChessMove) -> i32 {
+        let target_square = m.target_square;
+        let mut occupied = (self.white_pieces | self.black_pieces) & !m.start_square.bit_board();
+
+        let mut gain = [0; 32];
+        let mut piece_on_target = SEE_VALUES[PieceType::from_cpt(m.move_piece_type) as usize];
+
+        gain[0] = SEE_VALUES[PieceType::from_cpt(m.capture_piece_type) as usize];
+
+        if m.is_en_passant() {
+            let captured_square = if m.move_piece_type.is_white() { target_square as u8 - 8 } else { target_square as u8 + 8 };
+            
+            occupied &= !Square::from_u8(captured_square).bit_board();
+            gain[0] = SEE_VALUES[PieceType::Pawn as usize];
+        }
+
+        if m.is_promotion() {
+            piece_on_target = SEE_VALUES[PieceType::from_cpt(m.promotion_piece_type) as usize];
+            gain[0] += piece_on_target - SEE_VALUES[PieceType::Pawn as usize];
+        }
+
+        let mut white = !m.move_piece_type.is_white();
+        let mut depth = 0;
+
+        loop {
+            depth += 1;
+
+            //Only counts if the side to move at this depth finds a piece to recapture with
+            gain[depth] = piece_on_target - gain[depth - 1];
+
+            //Neither side can improve by continuing the exchange
+            if cmp::max(-gain[depth - 1], gain[depth]) < 0 || depth + 1 == gain.len() {
+                break;
+            }
+
+            let color_mask = if white { self.white_pieces } else { self.black_pieces };
+            let (attacker, attacker_value) = self.get_least_valuable_attacker(self.get_attackers_to(target_square, occupied) & color_mask);
+
+            if attacker == 0 {
+                break;
+            }
+
+            //The king can only recapture if the square is not defended anymore
+            if attacker_value == SEE_VALUES[PieceType::King as usize] && self.get_attackers_to(target_square, occupied & !attacker) & !color_mask != 0 {
+                break;
+            }
+
+            piece_on_target = attacker_value;
+            occupied &= !attacker;
+            white = !white;
+        }
+
+        while depth > 1 {
+            depth -= 1;
+            gain[depth - 1] = -cmp::max(-gain[depth - 1], gain[depth]);
+        }
+
+        return gain[0];
     }
 
     fn get_ray_attackers(&self, white: bool, target_square: Square) -> ArrayVec<Square, 16> {
@@ -683,8 +771,9 @@ impl BitBoard {
         let all_pieces = opposing_pieces | allied_pieces;
         let king_square = self.get_king_square(white);
         
-        let diagonal_attackers = self.diagonal_sliders & opposing_pieces & bitboard_helper::DIAGONAL_ATTACKS[king_square as usize];
-        let orthogonal_attackers = self.orthogonal_sliders & opposing_pieces & bitboard_helper::ORTHOGONAL_ATTACKS[king_square as usize];
+        //Only sliders that would see the king if our own pieces were removed can pin
+        let diagonal_attackers = self.diagonal_sliders & opposing_pieces & magic_bitboard::bishop_attacks(king_square, opposing_pieces);
+        let orthogonal_attackers = self.orthogonal_sliders & opposing_pieces & magic_bitboard::rook_attacks(king_square, opposing_pieces);
 
         let d_res = get_slide_pins(king_square, diagonal_attackers, all_pieces);
         let o_res = get_slide_pins(king_square, orthogonal_attackers, all_pieces);
@@ -759,21 +848,38 @@ impl BitBoard {
     }
 
     pub fn generate_legal_moves(&self, whites_turn: bool) -> ArrayVec<ChessMove, 200> {
+        return self.generate_moves(whites_turn, MoveGenType::All);
+    }
+
+    //Target squares of the pieces and of the pawn pushes that belong to the generated kind of moves
+    fn get_target_masks(&self, whites_turn: bool, gen_type: MoveGenType) -> (u64, u64) {
+        let opponent_mask = if !whites_turn { self.white_pieces } else { self.black_pieces };
+        let promotion_mask = bitboard_helper::RANK_MASKS[if whites_turn { 7 } else { 0 }];
+
+        return match gen_type {
+            MoveGenType::All => (u64::MAX, u64::MAX),
+            MoveGenType::Captures => (opponent_mask, promotion_mask),
+            MoveGenType::Quiets => (!opponent_mask, !promotion_mask),
+        };
+    }
+
+    fn generate_moves(&self, whites_turn: bool, gen_type: MoveGenType) -> ArrayVec<ChessMove, 200> {
         let attacker_list = self.get_square_attacker(!whites_turn, self.get_king_square(whites_turn));
+        let target_mask = self.get_target_masks(whites_turn, gen_type).0;
         
         //Double check
         if attacker_list.len() == 2 {
             //println!("Double check");
-            return self.generate_legal_king_moves(whites_turn);
+            return self.generate_legal_king_moves(whites_turn, target_mask);
         }
         
         
         if attacker_list.len() == 1 {
             //println!("Single check");
-            return self.generate_legal_moves_in_check(attacker_list[0], whites_turn);
+            return self.generate_legal_moves_in_check(attacker_list[0], whites_turn, gen_type);
         }
 
-        return self.generate_legal_moves_no_check(whites_turn);
+        return self.generate_legal_moves_no_check(whites_turn, gen_type);
         
         //let mut list = self.generate_legal_moves_no_check(whites_turn);
         //let mut fast = self.generate_legal_moves_no_check_fast(whites_turn);
@@ -813,7 +919,7 @@ impl BitBoard {
     }   
     
     //Only king moves
-    fn generate_legal_king_moves(&self, whites_turn: bool) -> ArrayVec<ChessMove, 200> {
+    fn generate_legal_king_moves(&self, whites_turn: bool, target_mask: u64) -> ArrayVec<ChessMove, 200> {
         let mut list = ArrayVec::new();
         
         let moving_color_mask = if whites_turn { self.white_pieces } else { self.black_pieces };
@@ -826,7 +932,7 @@ impl BitBoard {
         let king_square = self.get_king_square(whites_turn);
                 
         for target_index in bitboard_helper::iterate_set_bits(
-            bitboard_helper::KING_ATTACKS[king_square as usize] & !moving_color_mask) {
+            bitboard_helper::KING_ATTACKS[king_square as usize] & !moving_color_mask & target_mask) {
 
             let target_square = Square::from_u8(target_index as u8);
 
@@ -840,8 +946,9 @@ impl BitBoard {
         return list;
     } 
 
-    fn generate_legal_moves_in_check(&self, attacker_square: Square, whites_turn: bool) -> ArrayVec<ChessMove, 200> {
+    fn generate_legal_moves_in_check(&self, attacker_square: Square, whites_turn: bool, gen_type: MoveGenType) -> ArrayVec<ChessMove, 200> {
         let mut list = ArrayVec::new();
+        let (target_mask, push_mask) = self.get_target_masks(whites_turn, gen_type);
 
         let attacker_type = PieceType::from_cpt(self.type_field[attacker_square as usize]);
         let king_square = self.get_king_square(whites_turn);
@@ -855,6 +962,9 @@ impl BitBoard {
             possible_move_mask |= bitboard_helper::get_in_between(attacker_square, king_square);
         }
 
+        let pawn_push_mask = possible_move_mask & push_mask;
+        possible_move_mask &= target_mask;
+
         //Pinned pieces cant block check
         let moving_unpinned_pieces = !pinned_pieces & if whites_turn { self.white_pieces } else { self.black_pieces };
         let opponent_mask = if !whites_turn { self.white_pieces } else { self.black_pieces };
@@ -878,13 +988,13 @@ impl BitBoard {
         let pawn_direction: i32 = if whites_turn { 1 } else { -1 };
 
         let pawns = self.pawns & moving_unpinned_pieces;
-        let ep_mask = if self.en_passant_square == Square::None { 0 } else { self.en_passant_square.bit_board() };
+        let ep_mask = if self.en_passant_square == Square::None || gen_type == MoveGenType::Quiets { 0 } else { self.en_passant_square.bit_board() };
 
         let mut move_piece_type = ColoredPieceType::from_pt(PieceType::Pawn, whites_turn);
 
         let mut res = !all_mask & bitboard_helper::shift_board(pawns, 0,  pawn_direction);
         
-        for index in bitboard_helper::iterate_set_bits(possible_move_mask & res) {
+        for index in bitboard_helper::iterate_set_bits(pawn_push_mask & res) {
             let target_square = Square::from_u8(index as u8);
             let start_square = Square::from_u8((index as i32 - pawn_direction * 8) as u8);
 
@@ -893,7 +1003,7 @@ impl BitBoard {
 
         let double_move_mask = bitboard_helper::RANK_MASKS[if whites_turn { 3  } else { 4 }];      
         //double move                                                            only successfull first moves
-        res = double_move_mask & possible_move_mask & !all_mask & bitboard_helper::shift_board(res, 0,  pawn_direction);
+        res = double_move_mask & pawn_push_mask & !all_mask & bitboard_helper::shift_board(res, 0,  pawn_direction);
 
         for index in bitboard_helper::iterate_set_bits(res) {
             let target_square = Square::from_u8(index as u8);
@@ -957,44 +1067,39 @@ impl BitBoard {
         //Diagonal attackers
         let diagonal_attackers = self.diagonal_sliders & moving_unpinned_pieces;
         for start_index in bitboard_helper::iterate_set_bits(diagonal_attackers) {
-            let intersection = possible_move_mask & bitboard_helper::DIAGONAL_ATTACKS[start_index as usize];
             let start_square = Square::from_u8(start_index as u8);
+            let intersection = possible_move_mask & magic_bitboard::bishop_attacks(start_square, all_mask);
 
             for target_index in bitboard_helper::iterate_set_bits(intersection) {
                 let target_square = Square::from_u8(target_index as u8);
-                let in_between = bitboard_helper::get_in_between(start_square, target_square);
-                if  in_between & all_mask == 0 {
-                    list.push(ChessMove::new_move(start_square, target_square, 
-                        self.type_field[start_square as usize], self.type_field[target_square as usize]));
-                }
+                list.push(ChessMove::new_move(start_square, target_square, 
+                    self.type_field[start_square as usize], self.type_field[target_square as usize]));
             }
         }
 
         let orthogonal_attackers = self.orthogonal_sliders & moving_unpinned_pieces;
         //Orthogonal attackers
         for start_index in bitboard_helper::iterate_set_bits(orthogonal_attackers) {
-            let intersection = possible_move_mask & bitboard_helper::ORTHOGONAL_ATTACKS[start_index as usize];
             let start_square = Square::from_u8(start_index as u8);
+            let intersection = possible_move_mask & magic_bitboard::rook_attacks(start_square, all_mask);
 
             for target_index in bitboard_helper::iterate_set_bits(intersection) {
                 let target_square = Square::from_u8(target_index as u8);
-                let in_between = bitboard_helper::get_in_between(start_square, target_square);
-                if  in_between & all_mask == 0 {
-                    list.push(ChessMove::new_move(start_square, target_square, 
-                        self.type_field[start_square as usize], self.type_field[target_square as usize]));
-                }
+                list.push(ChessMove::new_move(start_square, target_square, 
+                    self.type_field[start_square as usize], self.type_field[target_square as usize]));
             }
         }
 
-        for m in self.generate_legal_king_moves(whites_turn) {
+        for m in self.generate_legal_king_moves(whites_turn, target_mask) {
             list.push(m);
         }
 
         return list;
     }
 
-    fn generate_legal_moves_no_check(&self, whites_turn: bool) -> ArrayVec<ChessMove, 200> {
+    fn generate_legal_moves_no_check(&self, whites_turn: bool, gen_type: MoveGenType) -> ArrayVec<ChessMove, 200> {
         let mut list = ArrayVec::new();
+        let (target_mask, push_mask) = self.get_target_masks(whites_turn, gen_type);
 
         let king_square = self.get_king_square(whites_turn);
 
@@ -1024,6 +1129,8 @@ impl BitBoard {
 
         let pawn_direction: i32 = if whites_turn { 1 } else { -1 };
         let ep_mask = if self.en_passant_square == Square::None { 0 } else { self.en_passant_square.bit_board() };
+        //Pawn captures and en passant are never quiet
+        let pawn_capture_mask = if gen_type == MoveGenType::Quiets { 0 } else { opponent_mask | ep_mask };
 
         let pawns = self.pawns & moving_color;
         let forward_movable_pawns = pawns & !d_pins & !(o_pins & bitboard_helper::RANK_MASKS[king_square.rank() as usize]);
@@ -1035,7 +1142,7 @@ impl BitBoard {
 
         let mut res = !all_mask & bitboard_helper::shift_board(forward_movable_pawns, 0,  pawn_direction);
         
-        for index in bitboard_helper::iterate_set_bits(res) {
+        for index in bitboard_helper::iterate_set_bits(res & push_mask) {
             let target_square = Square::from_u8(index as u8);
             let start_square = Square::from_u8((index as i32 - pawn_direction * 8) as u8);
 
@@ -1043,7 +1150,7 @@ impl BitBoard {
         }   
         
         //double move                                                            only successfull first moves
-        res = double_move_mask & !all_mask & bitboard_helper::shift_board(res, 0,  pawn_direction);
+        res = double_move_mask & push_mask & !all_mask & bitboard_helper::shift_board(res, 0,  pawn_direction);
 
         for index in bitboard_helper::iterate_set_bits(res) {
             let target_square = Square::from_u8(index as u8);
@@ -1055,7 +1162,7 @@ impl BitBoard {
         //attack right
         let diagonal_movable_pawns = pawns & !o_pins;
 
-        res = (opponent_mask | ep_mask) & bitboard_helper::shift_board(diagonal_movable_pawns, 1,  pawn_direction);
+        res = pawn_capture_mask & bitboard_helper::shift_board(diagonal_movable_pawns, 1,  pawn_direction);
         for index in bitboard_helper::iterate_set_bits(res) {
             let target_square = Square::from_u8(index as u8);
             let start_square = Square::from_u8((index as i32 - pawn_direction * 8 - 1) as u8);
@@ -1081,7 +1188,7 @@ impl BitBoard {
         }
 
         //attack left
-        res = (opponent_mask | ep_mask) & bitboard_helper::shift_board(diagonal_movable_pawns, -1,  pawn_direction);
+        res = pawn_capture_mask & bitboard_helper::shift_board(diagonal_movable_pawns, -1,  pawn_direction);
         for index in bitboard_helper::iterate_set_bits(res) {
             let target_square = Square::from_u8(index as u8);
             let start_square = Square::from_u8((index as i32 - pawn_direction * 8 + 1) as u8);
@@ -1112,7 +1219,7 @@ impl BitBoard {
             let start_square = Square::from_u8(start_index as u8);
 
             for target_index in bitboard_helper::iterate_set_bits(
-                bitboard_helper::KNIGHT_ATTACKS[start_square as usize] & !moving_color) {
+                bitboard_helper::KNIGHT_ATTACKS[start_square as usize] & !moving_color & target_mask) {
 
                 let target_square = Square::from_u8(target_index as u8);
 
@@ -1121,91 +1228,40 @@ impl BitBoard {
             }
         }
 
-        fn add_slide_moves(start_square: Square, move_piece_type: ColoredPieceType, dx: i32, dy: i32, type_field: [ColoredPieceType; 64], white: bool, list: &mut ArrayVec<ChessMove, 200>) {
-            let mut x = start_square.file() as i32 + dx; 
-            let mut y = start_square.rank() as i32 + dy;
-
-            while x >= 0 && x < 8 && y >= 0 && y < 8 {
-                let target_square = Square::from_u8((x + y * 8) as u8);
-                let target_piece_type = type_field[target_square as usize];
-
-                if target_piece_type == ColoredPieceType::None || target_piece_type.is_white() != move_piece_type.is_white() {
-                    list.push(ChessMove::new_move(start_square, target_square, move_piece_type, target_piece_type));
-                }
-
-                if target_piece_type != ColoredPieceType::None {
-                    break;
-                }
+        //Pinned sliders can only move along the line between their king and the pinning piece
+        fn add_slide_moves(start_square: Square, targets: u64, type_field: &[ColoredPieceType; 64], list: &mut ArrayVec<ChessMove, 200>) {
+            let move_piece_type = type_field[start_square as usize];
 
-                x += dx;
-                y += dy;
+            for target_index in bitboard_helper::iterate_set_bits(targets) {
+                list.push(ChessMove::new_move(start_square, Square::from_u8(target_index as u8), move_piece_type, type_field[target_index as usize]));
             }
         }
 
         //diagonal moves
-        const DIAGONAL_DIRECTIONS: [(i32, i32); 4] = [
-            (1, 1),
-            (1, -1),
-            (-1, 1),
-            (-1, -1),
-        ];
-
         for start_index in bitboard_helper::iterate_set_bits(self.diagonal_sliders & moving_color & !o_pins) {
             let start_square = Square::from_u8(start_index as u8);
-            move_piece_type = self.type_field[start_square as usize];
-            
-            if bitboard_helper::get_bit(d_pins, start_square) {
-                let x = start_square.file() as i32 - king_square.file() as i32;
-                let y = start_square.rank() as i32 - king_square.rank() as i32;
-
-                for (dx, dy) in DIAGONAL_DIRECTIONS {
-                    //Dot product zero -> vertical to pin direction
-                    if dx * x + dy * y == 0 {
-                        continue;
-                    }
+            let mut targets = magic_bitboard::bishop_attacks(start_square, all_mask) & !moving_color & target_mask;
 
-                    add_slide_moves(start_square, move_piece_type, dx, dy, self.type_field, whites_turn, &mut list);
-                }
-            } 
-            else {
-                for (dx, dy) in DIAGONAL_DIRECTIONS {
-                    add_slide_moves(start_square, move_piece_type, dx, dy, self.type_field, whites_turn, &mut list);
-                }
+            if bitboard_helper::get_bit(d_pins, start_square) {
+                targets &= bitboard_helper::DIAGONAL_ATTACKS[start_square as usize] & bitboard_helper::DIAGONAL_ATTACKS[king_square as usize];
             }
-        }
 
-        const ORTHOGONAL_DIRECTIONS: [(i32, i32); 4] = [
-            (1, 0),
-            (-1, 0),
-            (0, 1),
-            (0, -1),
-        ];
+            add_slide_moves(start_square, targets, &self.type_field, &mut list);
+        }
 
+        //orthogonal moves
         for start_index in bitboard_helper::iterate_set_bits(self.orthogonal_sliders & moving_color & !d_pins) {
             let start_square = Square::from_u8(start_index as u8);
-            move_piece_type = self.type_field[start_square as usize];
-            
-            if bitboard_helper::get_bit(o_pins, start_square) {
-                let x = start_square.file() as i32 - king_square.file() as i32;
-                let y = start_square.rank() as i32 - king_square.rank() as i32;
-
-                for (dx, dy) in ORTHOGONAL_DIRECTIONS {
-                    //Dot product zero -> vertical to pin direction
-                    if dx * x + dy * y == 0 {
-                        continue;
-                    }
+            let mut targets = magic_bitboard::rook_attacks(start_square, all_mask) & !moving_color & target_mask;
 
-                    add_slide_moves(start_square, move_piece_type, dx, dy, self.type_field, whites_turn, &mut list);
-                }
-            } 
-            else {
-                for (dx, dy) in ORTHOGONAL_DIRECTIONS {
-                    add_slide_moves(start_square, move_piece_type, dx, dy, self.type_field, whites_turn, &mut list);
-                }
+            if bitboard_helper::get_bit(o_pins, start_square) {
+                targets &= bitboard_helper::ORTHOGONAL_ATTACKS[start_square as usize] & bitboard_helper::ORTHOGONAL_ATTACKS[king_square as usize];
             }
+
+            add_slide_moves(start_square, targets, &self.type_field, &mut list);
         }
 
-        for m in self.generate_legal_king_moves(whites_turn) {
+        for m in self.generate_legal_king_moves(whites_turn, target_mask) {
             list.push(m);
         }
 
@@ -1213,6 +1269,10 @@ impl BitBoard {
 
         //Castles
         //not in check
+        if gen_type == MoveGenType::Captures {
+            return list;
+        }
+
         if whites_turn {
             if self.white_queen_castle {
                 if  bitboard_helper::WHITE_QUEEN_CASTLE_MASK & all_mask == 0 && 
@@ -1390,93 +1450,48 @@ impl BitBoard {
             }
         }
 
-        fn add_slide_moves(start_square: Square, move_piece_type: ColoredPieceType, dx: i32, dy: i32, type_field: [ColoredPieceType; 64], white: bool, list: &mut ArrayVec<ChessMove, 200>) {
-            let mut x = start_square.file() as i32 + dx; 
-            let mut y = start_square.rank() as i32 + dy;
-
-            while x >= 0 && x < 8 && y >= 0 && y < 8 {
-                let target_square = Square::from_u8((x + y * 8) as u8);
-                let target_piece_type = type_field[target_square as usize];
+        //Includes defended own pieces, pinned sliders only move along the pin line
+        fn add_slide_moves(start_square: Square, targets: u64, type_field: &[ColoredPieceType; 64], list: &mut ArrayVec<ChessMove, 200>) {
+            let move_piece_type = type_field[start_square as usize];
 
-                list.push(ChessMove::new_move(start_square, target_square, move_piece_type, target_piece_type));
-                
-                if target_piece_type != ColoredPieceType::None && 
-                        //Both diagonal sliders
-                    !(  (move_piece_type.is_diagonal_slider() && target_piece_type.is_diagonal_slider() 
-                        //Both are orthogonal sliders 
-                            || move_piece_type.is_orthogonal_slider() && target_piece_type.is_orthogonal_slider()) 
-                            && target_piece_type.is_white() == move_piece_type.is_white()
-                    ) {
-                
-                    break;
-                }
-
-                x += dx;
-                y += dy;
+            for target_index in bitboard_helper::iterate_set_bits(targets) {
+                list.push(ChessMove::new_move(start_square, Square::from_u8(target_index as u8), move_piece_type, type_field[target_index as usize]));
             }
         }
 
-        //diagonal moves
-        const DIAGONAL_DIRECTIONS: [(i32, i32); 4] = [
-            (1, 1),
-            (1, -1),
-            (-1, 1),
-            (-1, -1),
-        ];
+        //Own sliders of the same kind are seen through, a queen sees through both kinds
+        let own_diagonal = self.diagonal_sliders & moving_color;
+        let own_orthogonal = self.orthogonal_sliders & moving_color;
+        let x_ray_occupancy = |start_square: Square| {
+            let piece_type = self.type_field[start_square as usize];
 
+            return all_mask
+                & !if piece_type.is_diagonal_slider() { own_diagonal } else { 0 }
+                & !if piece_type.is_orthogonal_slider() { own_orthogonal } else { 0 };
+        };
+
+        //diagonal moves
         for start_index in bitboard_helper::iterate_set_bits(self.diagonal_sliders & moving_color & !o_pins) {
             let start_square = Square::from_u8(start_index as u8);
-            move_piece_type = self.type_field[start_square as usize];
-            
-            if bitboard_helper::get_bit(d_pins, start_square) {
-                let x = start_square.file() as i32 - king_square.file() as i32;
-                let y = start_square.rank() as i32 - king_square.rank() as i32;
+            let mut targets = magic_bitboard::bishop_attacks(start_square, x_ray_occupancy(start_square));
 
-                for (dx, dy) in DIAGONAL_DIRECTIONS {
-                    //Dot product zero -> vertical to pin direction
-                    if dx * x + dy * y == 0 {
-                        continue;
-                    }
-
-                    add_slide_moves(start_square, move_piece_type, dx, dy, self.type_field, whites_turn, &mut list);
-                }
-            } 
-            else {
-                for (dx, dy) in DIAGONAL_DIRECTIONS {
-                    add_slide_moves(start_square, move_piece_type, dx, dy, self.type_field, whites_turn, &mut list);
-                }
+            if bitboard_helper::get_bit(d_pins, start_square) {
+                targets &= bitboard_helper::DIAGONAL_ATTACKS[start_square as usize] & bitboard_helper::DIAGONAL_ATTACKS[king_square as usize];
             }
-        }
 
-        const ORTHOGONAL_DIRECTIONS: [(i32, i32); 4] = [
-            (1, 0),
-            (-1, 0),
-            (0, 1),
-            (0, -1),
-        ];
+            add_slide_moves(start_square, targets, &self.type_field, &mut list);
+        }
 
+        //orthogonal moves
         for start_index in bitboard_helper::iterate_set_bits(self.orthogonal_sliders & moving_color & !d_pins) {
             let start_square = Square::from_u8(start_index as u8);
-            move_piece_type = self.type_field[start_square as usize];
-            
-            if bitboard_helper::get_bit(o_pins, start_square) {
-                let x = start_square.file() as i32 - king_square.file() as i32;
-                let y = start_square.rank() as i32 - king_square.rank() as i32;
+            let mut targets = magic_bitboard::rook_attacks(start_square, x_ray_occupancy(start_square));
 
-                for (dx, dy) in ORTHOGONAL_DIRECTIONS {
-                    //Dot product zero -> vertical to pin direction
-                    if dx * x + dy * y == 0 {
-                        continue;
-                    }
-
-                    add_slide_moves(start_square, move_piece_type, dx, dy, self.type_field, whites_turn, &mut list);
-                }
-            } 
-            else {
-                for (dx, dy) in ORTHOGONAL_DIRECTIONS {
-                    add_slide_moves(start_square, move_piece_type, dx, dy, self.type_field, whites_turn, &mut list);
-                }
+            if bitboard_helper::get_bit(o_pins, start_square) {
+                targets &= bitboard_helper::ORTHOGONAL_ATTACKS[start_square as usize] & bitboard_helper::ORTHOGONAL_ATTACKS[king_square as usize];
             }
+
+            add_slide_moves(start_square, targets, &self.type_field, &mut list);
         }
 
         move_piece_type = ColoredPieceType::from_pt(PieceType::King, whites_turn);   
@@ -1567,46 +1582,17 @@ impl BitBoard {
 
     pub fn get_queen_moves(&self, start_square: Square) -> u64
     {
-        //diagonal moves
-        const QUEEN_DIRECTIONS: [(i32, i32); 8] = [
-            (1, 1),
-            (1, -1),
-            (-1, 1),
-            (-1, -1),
-
-            (1, 0),
-            (-1, 0),
-            (0, 1),
-            (0, -1),
-        ];
-
-        let mut ret = 0;
-        for (dx, dy) in QUEEN_DIRECTIONS {
-            let mut x = start_square.file() as i32 + dx; 
-            let mut y = start_square.rank() as i32 + dy;
-
-            while x >= 0 && x < 8 && y >= 0 && y < 8 {
-                let target_square = Square::from_u8((x + y * 8) as u8);
-                let target_piece_type = self.type_field[target_square as usize];
-
-                bitboard_helper::set_bit(&mut ret, target_square, true);
-                
-                if target_piece_type != ColoredPieceType::None {
-                    break;
-                }
-
-                x += dx;
-                y += dy;
-            }
-        }
-
-        return ret;
+        return magic_bitboard::queen_attacks(start_square, self.white_pieces | self.black_pieces);
     }
 
     pub fn make_move(&mut self, m: ChessMove) {
+        //Pieces update the hash as they are moved, the rest of the state is swapped as a whole
+        self.zoberist_hash ^= self.get_state_hash();
+
         if m.is_null_move() {
             self.en_passant_square = Square::None;
             self.whites_turn = !self.whites_turn;
+            self.zoberist_hash ^= self.get_state_hash();
 
             //println!("kek");
 
@@ -1699,6 +1685,9 @@ impl BitBoard {
         }
 
         self.whites_turn = !self.whites_turn;
+        self.zoberist_hash ^= self.get_state_hash();
+
+        debug_assert_eq!(self.zoberist_hash, self.calculate_zoberist_hash(), "Incremental hash differs after {}", m.get_uci());
     }
 
     //Does not check castle move square and start square
@@ -1721,6 +1710,124 @@ impl BitBoard {
         return self.generate_legal_moves(self.whites_turn);        
     }
 
+    //Legal captures, en passant and promotions
+    pub fn get_legal_captures(&self) -> ArrayVec<ChessMove, 200> {
+        return self.generate_moves(self.whites_turn, MoveGenType::Captures);
+    }
+
+    //Every legal move get_legal_captures leaves out
+    pub fn get_legal_quiets(&self) -> ArrayVec<ChessMove, 200> {
+        return self.generate_moves(self.whites_turn, MoveGenType::Quiets);
+    }
+
+    //Tries the cheap king moves first, most positions never need the full generation
+    pub fn has_legal_move(&self) -> bool {
+        let target_mask = self.get_target_masks(self.whites_turn, MoveGenType::All).0;
+
+        return !self.generate_legal_king_moves(self.whites_turn, target_mask).is_empty()
+            || !self.get_legal_captures().is_empty() 
+            || !self.get_legal_quiets().is_empty();
+    }
+
+    //Exact below the limit, otherwise only a lower bound that reaches it
+    //Outside of check the moves of unpinned knights, sliders and single pawn pushes are all legal, so counting them is enough in almost every position
+    pub fn count_legal_moves(&self, limit: usize) -> usize {
+        if !self.in_check() {
+            let moving_color = if self.whites_turn { self.white_pieces } else { self.black_pieces };
+            let all_mask = self.white_pieces | self.black_pieces;
+            let (d_pins, o_pins) = self.get_pin_info(self.whites_turn);
+            let unpinned = moving_color & !(d_pins | o_pins);
+            let pawn_direction: i32 = if self.whites_turn { 1 } else { -1 };
+
+            let mut count = (bitboard_helper::shift_board(self.pawns & unpinned, 0, pawn_direction) & !all_mask).count_ones() as usize;
+
+            for square in bitboard_helper::iterate_set_bits((self.knights | self.diagonal_sliders | self.orthogonal_sliders) & unpinned) {
+                let square = Square::from_u8(square as u8);
+                let mut targets = 0;
+
+                if bitboard_helper::get_bit(self.knights, square) {
+                    targets |= bitboard_helper::KNIGHT_ATTACKS[square as usize];
+                }
+
+                if bitboard_helper::get_bit(self.diagonal_sliders, square) {
+                    targets |= magic_bitboard::bishop_attacks(square, all_mask);
+                }
+
+                if bitboard_helper::get_bit(self.orthogonal_sliders, square) {
+                    targets |= magic_bitboard::rook_attacks(square, all_mask);
+                }
+
+                count += (targets & !moving_color).count_ones() as usize;
+
+                if count >= limit {
+                    return count;
+                }
+            }
+
+            if count >= limit {
+                return count;
+            }
+        }
+
+        return self.get_legal_moves().len();
+    }
+
+    //For moves that do not come from the generator of this position, like hash moves and killers
+    pub fn is_legal_move(&self, m: ChessMove) -> bool {
+        let moving_color = if self.whites_turn { self.white_pieces } else { self.black_pieces };
+        let all_mask = self.white_pieces | self.black_pieces;
+
+        if m.is_null_move() || m.move_piece_type == ColoredPieceType::None || m.move_piece_type.is_white() != self.whites_turn 
+            || self.type_field[m.start_square as usize] != m.move_piece_type || bitboard_helper::get_bit(moving_color, m.target_square) {
+            return false;
+        }
+
+        //The castle conditions are left to the generator, castles are rare hash moves
+        if m.is_castle() {
+            return self.get_legal_quiets().contains(&m);
+        }
+
+        let piece_type = PieceType::from_cpt(m.move_piece_type);
+        let is_en_passant = piece_type == PieceType::Pawn && m.target_square == self.en_passant_square;
+
+        if m.capture_piece_type != self.type_field[m.target_square as usize] || (is_en_passant && m.is_direct_capture()) {
+            return false;
+        }
+
+        let promotion_rank = if self.whites_turn { 7 } else { 0 };
+        let needs_promotion = piece_type == PieceType::Pawn && m.target_square.rank() == promotion_rank;
+
+        if needs_promotion != m.is_promotion() {
+            return false;
+        }
+
+        if m.is_promotion() && (m.promotion_piece_type.is_white() != self.whites_turn 
+            || matches!(PieceType::from_cpt(m.promotion_piece_type), PieceType::Pawn | PieceType::King)) {
+            return false;
+        }
+
+        let targets = match piece_type {
+            PieceType::Pawn => {
+                let pawn_direction: i32 = if self.whites_turn { 1 } else { -1 };
+                let start_rank = if self.whites_turn { 1 } else { 6 };
+                let pawn_attacks = if self.whites_turn { bitboard_helper::WHITE_PAWN_ATTACKS } else { bitboard_helper::BLACK_PAWN_ATTACKS };
+
+                let single_push = bitboard_helper::shift_board(m.start_square.bit_board(), 0, pawn_direction) & !all_mask;
+                let double_push = if m.start_square.rank() == start_rank { bitboard_helper::shift_board(single_push, 0, pawn_direction) & !all_mask } else { 0 };
+                let ep_mask = if is_en_passant { self.en_passant_square.bit_board() } else { 0 };
+
+                single_push | double_push | pawn_attacks[m.start_square as usize] & (all_mask & !moving_color | ep_mask)
+            },
+            PieceType::Knight => bitboard_helper::KNIGHT_ATTACKS[m.start_square as usize],
+            PieceType::Bishop => magic_bitboard::bishop_attacks(m.start_square, all_mask),
+            PieceType::Rook => magic_bitboard::rook_attacks(m.start_square, all_mask),
+            PieceType::Queen => magic_bitboard::queen_attacks(m.start_square, all_mask),
+            _ => bitboard_helper::KING_ATTACKS[m.start_square as usize],
+        };
+
+        return bitboard_helper::get_bit(targets, m.target_square) && self.move_is_legal(m);
+    }
+
     pub fn print_type_field(type_field: &[ColoredPieceType; 64]) {
         const PIECE_CHAR: [char; 13] = ['P', 'p', 'N', 'n', 'B', 'b', 'R', 'r', 'Q', 'q', 'K', 'k', ' '];
         println!("   {}", String::from_utf8(vec![b'_'; 16]).unwrap());
@@ -1821,4 +1928,65 @@ impl BitBoard {
     
         println!("]");
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn see_of(fen: &str, uci: &str) -> i32 {
+        let board = BitBoard::from_fen(fen);
+        let m = board.get_legal_moves().into_iter().find(|m| m.get_uci() == uci).unwrap();
+
+        return board.see(m);
+    }
+
+    #[test]
+    fn test_see_undefended_pawn() {
+        assert_eq!(see_of("1k1r4/1pp4p/p7/4p3/8/P5P1/1PP4P/2K1R3 w - - 0 1", "e1e5"), 100);
+    }
+
+    #[test]
+    fn test_see_queen_takes_defended_pawn() {
+        assert_eq!(see_of("4k3/8/3p4/4p3/8/8/8/4Q1K1 w - - 0 1", "e1e5"), -800);
+    }
+
+    fn walk_moves(board: &BitBoard, depth: u8) {
+        if depth == 0 {
+            return;
+        }
+
+        for m in board.get_legal_moves() {
+            let mut next = *board;
+            next.make_move(m);
+
+            assert_eq!(next.get_zoberist_hash(), next.calculate_zoberist_hash());
+            assert_eq!(next.get_zoberist_hash(), BitBoard::from_fen(&next.get_fen()).get_zoberist_hash());
+
+            walk_moves(&next, depth - 1);
+        }
+    }
+
+    #[test]
+    fn test_incremental_hash_with_castles_en_passant_and_promotions() {
+        walk_moves(&BitBoard::from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1"), 2);
+        walk_moves(&BitBoard::from_fen("r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1"), 3);
+        walk_moves(&BitBoard::from_fen("rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3"), 2);
+    }
+
+    #[test]
+    fn test_see_with_x_ray_attackers() {
+        assert_eq!(see_of("1k1r3q/1ppn3p/p4b2/4p3/8/P2N2P1/1PP1R1BP/2K1Q3 w - - 0 1", "d3e5"), -200);
+    }
+
+    #[test]
+    fn test_count_legal_moves_is_exact_below_the_limit() {
+        //Few moves, a pinned rook and a position in check
+        for fen in ["7k/8/8/8/8/8/P7/K7 w - - 0 1", "k3r3/8/8/8/8/8/4R3/4K3 w - - 0 1", "4k3/8/8/8/8/8/4r3/R3K3 w Q - 0 1"] {
+            let board = BitBoard::from_fen(fen);
+
+            assert_eq!(board.count_legal_moves(100), board.get_legal_moves().len());
+        }
+
+        assert!(BitBoard::start_position().count_legal_moves(3) >= 3);
+    }
+}
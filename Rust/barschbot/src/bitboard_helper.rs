@@ -46,73 +46,6 @@ pub fn shift_board(value: u64, dx: i32, dy: i32) -> u64 {
     }
 }
 
-pub fn gen_rook_moves(mut pos: u64, free: u64) -> u64 {
-    let mask = ORTHOGONAL_ATTACKS[pos.trailing_zeros() as usize] & free;
-    
-    println!("Pos");
-    print_bitboard(pos);
-    println!("Mask kek");
-    print_bitboard(mask);
-
-    let mut res = 0;
-    while res != pos {
-        pos = res;
-
-        res |= RIGHT_MOVE_MASK[1] & (pos << 1);
-        res |= LEFT_MOVE_MASK[1] & (pos >> 1);
-
-        res |= (pos << 8);
-        res |= (pos >> 8);
-
-        res &= mask;
-    }
-
-    return res;
-}
-pub fn gen_bishop_moves(mut pos: u64, free: u64) -> u64 {
-    let mask = ORTHOGONAL_ATTACKS[pos.trailing_zeros() as usize] & free;
-    
-    let mut res = 0;
-    while res != pos {
-        pos = res;
-
-        res |= LEFT_MOVE_MASK[1] & (res << 7);
-        res |= RIGHT_MOVE_MASK[1] & (res << 9);
-
-        res |= LEFT_MOVE_MASK[1] & (res >> 9);
-        res |= RIGHT_MOVE_MASK[1] & (res >> 7);
-
-        res &= mask;
-    }
-
-    return res;
-}
-pub fn gen_queen_moves(mut square: u32, free: u64) -> u64 {
-    let mask = QUEEN_ATTACKS[square as usize]  & free;
-    let mut pos = 1 << square;
-        
-    let mut res = 0;
-    while res != pos {
-        pos = res;
-
-        res |= RIGHT_MOVE_MASK[1] & (res << 1);
-        res |= LEFT_MOVE_MASK[1] & (res >> 1);
-
-        res |= (pos << 8);
-        res |= (pos >> 8);
-
-        res |= LEFT_MOVE_MASK[1] & (res << 7);
-        res |= RIGHT_MOVE_MASK[1] & (res << 9);
-
-        res |= LEFT_MOVE_MASK[1] & (res >> 9);
-        res |= RIGHT_MOVE_MASK[1] & (res >> 7);
-
-        res &= mask;
-    }
-
-    return res;
-}
-
 pub fn order_bits(value: u64, mask: u64) -> u64 {
     unsafe {
         return core::arch::x86_64::_pext_u64(value, mask);
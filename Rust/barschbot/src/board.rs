@@ -322,7 +322,7 @@ impl Board {
     
     pub fn make_move(&mut self, m: &ChessMove) {
 
-        self.zoberist_hash.update_hash(*m, self.en_passant_square, self.white_queen_castle, self.white_king_castle, self.black_queen_castle, self.black_king_castle);
+        self.zoberist_hash.update_hash(*m, &self.type_field, self.en_passant_square, self.white_queen_castle, self.white_king_castle, self.black_queen_castle, self.black_king_castle);
 
         if m.move_piece_type == constants::WHITE_KING {
             self.white_queen_castle = false;
@@ -153,3 +153,84 @@ impl ChessMove {
         }
     }
 }
+
+//Start and target square plus the promotion piece in 16 bits, for storing moves in the transposition table and the opening book
+//The piece types come back from the board the move is played on, see unpack
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct PackedMove(u16);
+
+pub const NULL_PACKED_MOVE: PackedMove = PackedMove(0);
+
+impl PackedMove {
+    pub fn from_u16(value: u16) -> PackedMove {
+        return PackedMove(value);
+    }
+
+    pub fn get_value(&self) -> u16 {
+        return self.0;
+    }
+
+    pub fn is_null_move(&self) -> bool {
+        return *self == NULL_PACKED_MOVE;
+    }
+
+    //Lossless for every move that can be played on board, anything else becomes the null move
+    pub fn unpack(&self, board: &BitBoard) -> ChessMove {
+        if self.is_null_move() {
+            return NULL_MOVE;
+        }
+
+        let start_square = Square::from_u8((self.0 & 0x3F) as u8);
+        let target_square = Square::from_u8((self.0 >> 6 & 0x3F) as u8);
+        let move_piece_type = board.get_piece_type(start_square);
+
+        //Can happen after a hash collision in the transposition table
+        if move_piece_type == ColoredPieceType::None || move_piece_type.is_white() != board.is_whites_turn() {
+            return NULL_MOVE;
+        }
+
+        let promotion_piece_type = match self.0 >> 12 {
+            0 => ColoredPieceType::None,
+            pt => ColoredPieceType::from_pt(PieceType::from_u8(pt as u8), move_piece_type.is_white()),
+        };
+
+        return ChessMove::new_pawn_move(start_square, target_square, move_piece_type, board.get_piece_type(target_square), promotion_piece_type);
+    }
+}
+
+impl ChessMove {
+    pub fn pack(&self) -> PackedMove {
+        if self.is_null_move() {
+            return NULL_PACKED_MOVE;
+        }
+
+        let promotion = if self.is_promotion() { PieceType::from_cpt(self.promotion_piece_type) as u16 } else { 0 };
+
+        return PackedMove(self.start_square as u16 | (self.target_square as u16) << 6 | promotion << 12);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn packed_moves_unpack_to_the_same_move() {
+        let fens = [
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3",
+            "n1n5/PPPk4/8/8/8/8/4Kppp/5N1N b - - 0 1",
+        ];
+
+        for fen in fens {
+            let board = BitBoard::from_fen(fen);
+
+            for m in board.get_legal_moves() {
+                assert!(m.pack().unpack(&board) == m, "{} in {}", m.get_uci(), fen);
+            }
+        }
+
+        assert!(NULL_MOVE.pack().is_null_move());
+        assert!(NULL_PACKED_MOVE.unpack(&BitBoard::from_fen(fens[0])).is_null_move());
+    }
+}
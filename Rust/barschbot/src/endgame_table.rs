@@ -559,7 +559,7 @@ impl EndgameTable {
                     }
                 }
 
-                table_map.insert(board.get_zoberist_hash(), val);
+                table_map.insert(board.get_legacy_hash(), val);
             }
 
             println!("Init cm: {}, draw: {}", check_mate_count, draw_count);
@@ -587,7 +587,7 @@ impl EndgameTable {
                     //println!("######################## Board: ");
                     //board.print();
 
-                    let board_hash = board.get_zoberist_hash();
+                    let board_hash = board.get_legacy_hash();
                     let val =  table_map[&board_hash];
 
                     let moves = board.get_legal_moves();
@@ -611,7 +611,7 @@ impl EndgameTable {
                         let sym = buffer.get_board_state().get_lowest_symmetry();                       
                         buffer = BitBoard::from_board_state(&sym);
                         
-                        let mut s = table_map[&buffer.get_zoberist_hash()];
+                        let mut s = table_map[&buffer.get_legacy_hash()];
 
                         if s == UNDEFINED {
                             all_defined = false;
@@ -731,12 +731,19 @@ impl EndgameTable {
 
         let start = time::Instant::now();
 
-        let mut file = File::open("C:\\Users\\hmart\\Documents\\GitHub\\Chess-Challenge\\Rust\\barschbot\\sorted_table_base_".to_owned() + &max_piece_count.to_string().to_owned() + ".bin").unwrap();
+        let path = "C:\\Users\\hmart\\Documents\\GitHub\\Chess-Challenge\\Rust\\barschbot\\sorted_table_base_".to_owned() + &max_piece_count.to_string().to_owned() + ".bin";
+
+        //The loading diagnostics go to stderr, UCI GUIs only expect protocol lines on stdout
+        let Ok(mut file) = File::open(&path) else {
+            eprintln!("No endgame table at {}, playing without it", path);
+            return EndgameTable { table_map: CompactHashmap::empty(), max_piece_count: 0 };
+        };
+
         // read the same file back into a Vec of bytes
         let mut buffer = Vec::<u8>::new();
         file.read_to_end(&mut buffer).unwrap();
 
-        println!("Loaded file in {} ms", start.elapsed().as_millis());
+        eprintln!("Loaded file in {} ms", start.elapsed().as_millis());
         let count = buffer.len() / 9;
 
         if buffer.len() % 9 != 0 {
@@ -755,7 +762,7 @@ impl EndgameTable {
 
             data.push((hash, score));
         }
-        println!("Finished loading data in {} ms", start.elapsed().as_millis());
+        eprintln!("Finished loading data in {} ms", start.elapsed().as_millis());
 
         for i in 1..data.len() {
             if data[i - 1].0 >= data[i].0 {
@@ -763,11 +770,11 @@ impl EndgameTable {
             }
         }
 
-        println!("Finished sorting data in {} ms", start.elapsed().as_millis());
+        eprintln!("Finished sorting data in {} ms", start.elapsed().as_millis());
 
         let mut table_map = CompactHashmap::new(data);
         
-        println!("Finished loading table in {} ms", start.elapsed().as_millis());
+        eprintln!("Finished loading table in {} ms", start.elapsed().as_millis());
 
         return EndgameTable { table_map, max_piece_count };        
     }
@@ -775,7 +782,7 @@ impl EndgameTable {
     pub fn get_score(&self, board: &BitBoard) -> i8 {
         let sym = BitBoard::from_board_state(& board.get_board_state().get_lowest_symmetry());      
 
-        let hash = sym.get_zoberist_hash();
+        let hash = sym.get_legacy_hash();
 
         if !self.table_map.contains_key(hash) {
             //board.print();
@@ -786,8 +793,7 @@ impl EndgameTable {
         let mut s = self.table_map.get(hash).unwrap();
 
         if s == UNDEFINED {
-            println!("Undefined: ");
-            board.print();
+            eprintln!("Undefined endgame table score: {}", board.get_fen());
 
             return 0;
         }
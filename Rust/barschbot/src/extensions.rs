@@ -0,0 +1,84 @@
+use crate::{bb_settings::BBSettings, bit_board::BitBoard, bitboard_helper, chess_move::ChessMove, colored_piece_type::ColoredPieceType, piece_type::PieceType};
+
+//Nodes with fewer legal moves than this extend every move
+pub const FEW_REPLIES: usize = 3;
+
+//Extension budget left on the current path, every kind is limited on its own and a disabled kind starts with none
+#[derive(Clone, Copy)]
+pub struct Extensions {
+    few_replies: u8,
+    check: u8,
+    recapture: u8,
+    passed_pawn: u8,
+    singular: u8,
+}
+
+impl Extensions {
+    pub fn new(settings: &BBSettings) -> Extensions {
+        return Extensions {
+            few_replies: settings.max_extensions,
+            check: if settings.check_extension { settings.check_extension_budget } else { 0 },
+            recapture: if settings.recapture_extension { settings.recapture_extension_budget } else { 0 },
+            passed_pawn: if settings.passed_pawn_extension { settings.passed_pawn_extension_budget } else { 0 },
+            singular: if settings.singular_extension { settings.singular_extension_budget } else { 0 },
+        };
+    }
+
+    pub fn can_extend_singular(&self) -> bool {
+        return self.singular > 0;
+    }
+
+    pub fn can_extend_few_replies(&self) -> bool {
+        return self.few_replies > 0;
+    }
+
+    //Budget for the child if m, already made on board, earns a one ply extension
+    //The first kind that applies and still has budget is used
+    pub fn extend(&self, board: &BitBoard, m: ChessMove, prev_move: ChessMove, singular_move: ChessMove, reply_count: usize) -> Option<Extensions> {
+        let mut next = *self;
+
+        let budget = if m == singular_move && next.singular > 0 {
+            &mut next.singular
+        }
+        else if next.check > 0 && board.in_check() {
+            &mut next.check
+        }
+        else if next.recapture > 0 && is_recapture(m, prev_move) {
+            &mut next.recapture
+        }
+        else if next.passed_pawn > 0 && is_passed_pawn_push(board, m) {
+            &mut next.passed_pawn
+        }
+        else if next.few_replies > 0 && reply_count < FEW_REPLIES {
+            &mut next.few_replies
+        }
+        else {
+            return None;
+        };
+
+        *budget -= 1;
+
+        return Some(next);
+    }
+}
+
+fn is_recapture(m: ChessMove, prev_move: ChessMove) -> bool {
+    return m.is_direct_capture() && prev_move.is_direct_capture() && m.target_square == prev_move.target_square;
+}
+
+//A pawn that reaches the 7th rank without enemy pawns in front of it or on the neighbouring files
+fn is_passed_pawn_push(board: &BitBoard, m: ChessMove) -> bool {
+    if PieceType::from_cpt(m.move_piece_type) != PieceType::Pawn {
+        return false;
+    }
+
+    let target = m.target_square as usize;
+
+    if m.is_white_move() {
+        return m.target_square.rank() == 6
+            && board.get_piece_bitboard(ColoredPieceType::BlackPawn) & bitboard_helper::WHITE_PASSED_PAWN_MASK[target] == 0;
+    }
+
+    return m.target_square.rank() == 1
+        && board.get_piece_bitboard(ColoredPieceType::WhitePawn) & bitboard_helper::BLACK_PASSED_PAWN_MASK[target] == 0;
+}
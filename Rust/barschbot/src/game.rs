@@ -36,6 +36,7 @@ impl GameState {
     }
 }
 
+#[derive(Clone)]
 pub struct Game {
     board_history: HashSet<u64>,
     board_stack: Vec<BitBoard>,
@@ -103,6 +104,13 @@ impl  Game {
         return self.move_stack.last().unwrap().is_null_move();
     }
 
+    pub fn last_move(&self) -> ChessMove {
+        return match self.move_stack.last() {
+            Some(m) => *m,
+            None => chess_move::NULL_MOVE,
+        };
+    }
+
     pub fn has_null_move(&self) -> bool {
         return self.move_stack.iter().any(|m| m.is_null_move());
     }
@@ -167,11 +175,91 @@ impl  Game {
     pub fn get_legal_moves(&mut self) -> ArrayVec<ChessMove, 200> {
         if !self.moves_generated {
             self.cached_moves = self.board.get_legal_moves();
+            self.moves_generated = true;
         }
 
         return self.cached_moves.clone();
     }
 
+    //Standard algebraic notation of a legal move in the current position
+    pub fn get_san(&mut self, m: ChessMove) -> String {
+        let mut s = "".to_owned();
+        let piece_type = PieceType::from_cpt(m.move_piece_type);
+
+        if m.is_castle() {
+            s += if (m.target_square as u8) < (m.start_square as u8) { "O-O-O" } else { "O-O" };
+        }
+        else {
+            if piece_type == PieceType::Pawn {
+                if m.is_capture() {
+                    s.push(m.start_square.file_char());
+                }
+            }
+            else {
+                s.push(piece_type.get_char());
+
+                //Other pieces of the same type that can reach the target square
+                let others = self.get_legal_moves().into_iter()
+                    .filter(|o| o.move_piece_type == m.move_piece_type && o.target_square == m.target_square && o.start_square != m.start_square)
+                    .collect::<Vec<_>>();
+
+                let start = m.start_square.to_string();
+
+                if !others.is_empty() {
+                    if others.iter().all(|o| o.start_square.file() != m.start_square.file()) {
+                        s += &start[0..1];
+                    }
+                    else if others.iter().all(|o| o.start_square.rank() != m.start_square.rank()) {
+                        s += &start[1..2];
+                    }
+                    else {
+                        s += &start;
+                    }
+                }
+            }
+
+            if m.is_capture() {
+                s += "x";
+            }
+
+            s += &m.target_square.to_string();
+
+            if m.is_promotion() {
+                s.push('=');
+                s.push(PieceType::from_cpt(m.promotion_piece_type).get_char());
+            }
+        }
+
+        self.make_move(m);
+
+        if self.get_game_state().is_checkmate() {
+            s += "#";
+        }
+        else if self.board.in_check() {
+            s += "+";
+        }
+
+        self.undo_move();
+
+        return s;
+    }
+
+    //SAN of a line of legal moves starting in the current position, separated by spaces
+    pub fn get_san_line(&mut self, line: &[ChessMove]) -> String {
+        let mut names = Vec::new();
+
+        for m in line {
+            names.push(self.get_san(*m));
+            self.make_move(*m);
+        }
+
+        for _ in line {
+            self.undo_move();
+        }
+
+        return names.join(" ");
+    }
+
     pub fn get_uci_move(&mut self, uci: String) -> ChessMove {
         let ml = self.get_legal_moves();
 
@@ -190,7 +278,10 @@ impl  Game {
 
     //[TODO] insuff material
     pub fn get_game_state(&mut self) -> GameState {
-        if self.get_legal_moves().len() == 0 {
+        //The search generates its moves in stages, the full list is only used when it is already there
+        let has_legal_move = if self.moves_generated { !self.cached_moves.is_empty() } else { self.board.has_legal_move() };
+
+        if !has_legal_move {
             if self.board.in_check() {
                 if self.is_whites_turn() {
                     return GameState::WhiteCheckmate;
@@ -216,3 +307,26 @@ impl  Game {
         return GameState::Undecided;
     }    
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn san_disambiguation_and_check() {
+        let mut game = Game::from_fen("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1");
+
+        let capture = game.get_uci_move("a1a8".to_owned());
+        assert_eq!(game.get_san(capture), "Rxa8+");
+
+        game.make_move(capture);
+        let king_move = game.get_uci_move("e8d7".to_owned());
+        game.make_move(king_move);
+
+        let rook_capture = game.get_uci_move("h1h8".to_owned());
+        assert_eq!(game.get_san(rook_capture), "Rhxh8");
+
+        let castle = game.get_uci_move("e1g1".to_owned());
+        assert_eq!(game.get_san(castle), "O-O");
+    }
+}
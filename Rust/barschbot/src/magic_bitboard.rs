@@ -0,0 +1,198 @@
+use std::sync::OnceLock;
+
+use crate::square::Square;
+
+//Slider attacks looked up from precomputed tables instead of walking every ray on each call
+//The table index is found with magic multiplication, or with the pext instruction when the "pext" feature is enabled
+//Build the pext variant with RUSTFLAGS="-C target-feature=+bmi2" on CPUs that have a fast pext (Intel Haswell+, AMD Zen 3+)
+
+#[cfg(all(feature = "pext", not(all(target_arch = "x86_64", target_feature = "bmi2"))))]
+compile_error!("the pext feature needs an x86_64 target with bmi2, build with RUSTFLAGS=\"-C target-feature=+bmi2\"");
+
+const ROOK_DIRECTIONS: [(i32, i32); 4] = [(1, 0), (-1, 0), (0, 1), (0, -1)];
+const BISHOP_DIRECTIONS: [(i32, i32); 4] = [(1, 1), (1, -1), (-1, 1), (-1, -1)];
+
+//Entries over all squares, every square needs 2^(relevant occupancy bits) of them
+const ROOK_TABLE_SIZE: usize = 102400;
+const BISHOP_TABLE_SIZE: usize = 5248;
+
+//Multipliers that map every relevant occupancy of a square to its own slot, or to one with the same attacks
+const ROOK_MAGICS: [u64; 64] = [1188950576646328352, 594485183902658560, 1801475035592919296, 612507141642650752, 4755818816406553088, 9295431834211451904, 144119590694707712, 144115738907640849, 289497015765697056, 70437467856896, 37717717765194816, 290622947814539904, 2814827093559328, 9820239733568307328, 4611967497699328512, 306807728944205956, 10412323438537490688, 18085042133667849, 141287512612873, 2449968093461479680, 2252349703979136, 1126174818369600, 4789472784896066, 10995140821252, 140739637952521, 2310346889087688705, 10952789488875602048, 1152939098940899456, 306807767564587040, 2377905003445747840, 576480560702030152, 4508040624768321, 2882444773891768368, 1157425241677365313, 4611721203084706048, 436849731067447296, 288371268300834816, 13907117850499220480, 562984346715137, 108658517241496577, 153157846589014016, 2909342952541274146, 281612483887168, 11029333363084886048, 1134696067006592, 4507997707468928, 2310909870196523012, 9439690110271619089, 38351208276099584, 585538322451988736, 76720623935554304, 576759830204122240, 162693155150366208, 1153484474043859456, 18155144587837568, 40954610201919616, 35815737032705, 342555201619771393, 144150372632498241, 8070468124503181569, 9223935124315310082, 3459045997526384647, 614778835619098628, 4611686191308472594];
+const BISHOP_MAGICS: [u64; 64] = [1157460297201095712, 9232383651341680648, 1175729775978873288, 5909853148649881736, 2451102138206519298, 1441437891287842816, 288379927181664260, 11294741796757536, 1513249167005257216, 9223688702679714368, 4611757491029213184, 9223939454681481472, 4616190787376513080, 1117245423616, 1513245793174292992, 9223381399892395296, 289638026181869824, 1154472031019073800, 2812498521377472529, 4631987418369659136, 6773270820945956, 844450718351617, 9224638884842377216, 9229041196125521920, 1935690489933888, 360574161325922304, 10385353517408731205, 618127844951851072, 1153204081294065664, 5765736722559139968, 1126184112956416, 583319242688530, 1157443263432492096, 659779619629056256, 2307540672497975332, 1157460426583703680, 11533719204043694592, 2348628653077794820, 567434981476369, 9235196193489858712, 2534720315342944, 4611829025840041985, 72093878999713792, 576461027454519808, 2305851878388285504, 5516349411426304130, 297294763468671042, 1339862671712846352, 143108348051464, 151736922669056, 3476779135970836513, 3476824001442498560, 5338173208468193354, 9227910831625340801, 9042595189657745, 31577999728976144, 18155720125325384, 141854192714784, 2256199001834561, 9457568014116586513, 4364206592, 140772130160768, 288321653082423808, 4629823716867646020];
+
+struct SliderTable {
+    masks: [u64; 64],
+    magics: [u64; 64],
+    shifts: [u32; 64],
+    offsets: [usize; 64],
+    attacks: Vec<u64>,
+}
+
+struct SliderTables {
+    rook: SliderTable,
+    bishop: SliderTable,
+}
+
+static TABLES: OnceLock<SliderTables> = OnceLock::new();
+
+fn get_tables() -> &'static SliderTables {
+    return TABLES.get_or_init(|| SliderTables {
+        rook: SliderTable::new(&ROOK_DIRECTIONS, ROOK_MAGICS, ROOK_TABLE_SIZE),
+        bishop: SliderTable::new(&BISHOP_DIRECTIONS, BISHOP_MAGICS, BISHOP_TABLE_SIZE),
+    });
+}
+
+//Builds the tables up front, so the first search does not pay for it
+pub fn init() {
+    get_tables();
+}
+
+pub fn rook_attacks(square: Square, occupied: u64) -> u64 {
+    return get_tables().rook.get_attacks(square as usize, occupied);
+}
+
+pub fn bishop_attacks(square: Square, occupied: u64) -> u64 {
+    return get_tables().bishop.get_attacks(square as usize, occupied);
+}
+
+pub fn queen_attacks(square: Square, occupied: u64) -> u64 {
+    let tables = get_tables();
+
+    return tables.rook.get_attacks(square as usize, occupied) | tables.bishop.get_attacks(square as usize, occupied);
+}
+
+impl SliderTable {
+    fn new(directions: &[(i32, i32); 4], magics: [u64; 64], size: usize) -> SliderTable {
+        let mut table = SliderTable { masks: [0; 64], magics, shifts: [0; 64], offsets: [0; 64], attacks: vec![0; size] };
+        let mut offset = 0;
+
+        for square in 0..64 {
+            //Squares on the edge of a ray never block anything behind them
+            let mask = get_relevant_mask(square, directions);
+            let bits = mask.count_ones();
+
+            table.masks[square] = mask;
+            table.shifts[square] = 64 - bits;
+            table.offsets[square] = offset;
+
+            //Every subset of the mask with the attacks it produces
+            let mut subset = 0_u64;
+            loop {
+                let index = offset + table.get_index(square, subset);
+                table.attacks[index] = get_ray_attacks(square, subset, directions);
+
+                subset = subset.wrapping_sub(mask) & mask;
+                if subset == 0 {
+                    break;
+                }
+            }
+
+            offset += 1 << bits;
+        }
+
+        assert_eq!(offset, size);
+
+        return table;
+    }
+
+    #[inline]
+    fn get_index(&self, square: usize, occupied: u64) -> usize {
+        let mask = self.masks[square];
+
+        if cfg!(feature = "pext") {
+            return pext(occupied, mask) as usize;
+        }
+
+        return ((occupied & mask).wrapping_mul(self.magics[square]) >> self.shifts[square]) as usize;
+    }
+
+    #[inline]
+    fn get_attacks(&self, square: usize, occupied: u64) -> u64 {
+        return self.attacks[self.offsets[square] + self.get_index(square, occupied)];
+    }
+}
+
+#[cfg(feature = "pext")]
+#[inline]
+fn pext(value: u64, mask: u64) -> u64 {
+    unsafe {
+        return core::arch::x86_64::_pext_u64(value, mask);
+    }
+}
+
+#[cfg(not(feature = "pext"))]
+fn pext(_value: u64, _mask: u64) -> u64 {
+    unreachable!();
+}
+
+//Walks every ray until it leaves the board or hits a piece, the blocking square is included
+fn get_ray_attacks(square: usize, occupied: u64, directions: &[(i32, i32); 4]) -> u64 {
+    let mut res = 0;
+
+    for (dx, dy) in directions {
+        let mut x = (square % 8) as i32 + dx;
+        let mut y = (square / 8) as i32 + dy;
+
+        while (0..8).contains(&x) && (0..8).contains(&y) {
+            let bit = 1_u64 << (x + y * 8);
+            res |= bit;
+
+            if occupied & bit != 0 {
+                break;
+            }
+
+            x += dx;
+            y += dy;
+        }
+    }
+
+    return res;
+}
+
+//Ray squares that can block, that is all except the last one of every ray
+fn get_relevant_mask(square: usize, directions: &[(i32, i32); 4]) -> u64 {
+    let mut res = 0;
+
+    for (dx, dy) in directions {
+        let mut x = (square % 8) as i32 + dx;
+        let mut y = (square / 8) as i32 + dy;
+
+        while (0..8).contains(&(x + dx)) && (0..8).contains(&(y + dy)) {
+            res |= 1_u64 << (x + y * 8);
+
+            x += dx;
+            y += dy;
+        }
+    }
+
+    return res;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check_every_occupancy(directions: &[(i32, i32); 4], get_attacks: fn(Square, u64) -> u64) {
+        for square in 0..64 {
+            let mask = get_relevant_mask(square, directions);
+
+            //Squares outside the mask must not change the result
+            let mut subset = 0_u64;
+            loop {
+                let occupied = subset | !mask & 0x8100_0000_0000_0081;
+                assert_eq!(get_attacks(Square::from_u8(square as u8), occupied), get_ray_attacks(square, occupied, directions));
+
+                subset = subset.wrapping_sub(mask) & mask;
+                if subset == 0 {
+                    break;
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn table_attacks_match_ray_walk() {
+        check_every_occupancy(&ROOK_DIRECTIONS, rook_attacks);
+        check_every_occupancy(&BISHOP_DIRECTIONS, bishop_attacks);
+    }
+}
@@ -34,8 +34,10 @@ use crate::dataset::EvalBoards;
 use crate::endgame_table::EndgameTable;
 use crate::opening_book::OpeningBook;
 use crate::perceptron::Perceptron;
+use crate::transposition_table::TranspositionTable;
 use crate::square::Square;
 
+use std::path::Path;
 use std::process::{Command, Stdio, Child};
 
 use rayon::prelude::*;
@@ -63,13 +65,49 @@ mod opening_book;
 mod match_handler;
 mod auto_tuning;
 mod compact_hashmap;
+mod time_manager;
+mod transposition_table;
+mod move_ordering;
+mod move_picker;
+mod extensions;
+mod score;
+mod bench;
+mod magic_bitboard;
 
 use std::env;
 fn main() {
     env::set_var("RUST_BACKTRACE", "1");
+    magic_bitboard::init();
+
+    //"bench [depth]", needs neither the endgame table nor the opening book
+    if env::args().nth(1).as_deref() == Some("bench") {
+        let depth = env::args().nth(2).and_then(|d| d.parse::<u8>().ok()).unwrap_or(bench::DEFAULT_BENCH_DEPTH);
+        bench::run_bench(depth.clamp(1, barsch_bot::MAX_SEARCH_DEPTH));
+        return;
+    }
 
     let (table, book) = load_files();
 
+    if env::args().nth(1).as_deref() == Some("uci") {
+        match_handler::uci_loop(&table, &book);
+        return;
+    }
+
+    //"tune [search|eval] [fens file]", tunes one parameter after the other in matches against stockfish, search parameters by default
+    if env::args().nth(1).as_deref() == Some("tune") {
+        let path = env::args().nth(3).unwrap_or("C:\\Users\\hmart\\Documents\\GitHub\\Chess-Challenge\\Rust\\data\\Fens.txt".to_string());
+        let fens = load_fens(&path);
+
+        if env::args().nth(2).as_deref() == Some("eval") {
+            auto_tuning::auto_tune(&fens, &book, &table, bb_settings::STANDARD_SETTINGS);
+        }
+        else {
+            auto_tuning::auto_tune_search(&fens, &book, &table, bb_settings::STANDARD_SETTINGS);
+        }
+
+        return;
+    }
+
     /* 
     let fens = load_fens("C:\\Users\\hmart\\Documents\\GitHub\\Chess-Challenge\\Rust\\data\\Fens.txt");
     let a = bb_settings::STANDARD_SETTINGS;
@@ -82,7 +120,8 @@ fn main() {
     auto_tuning::print_confidence(w, d, l);
     */
     
-    play_all_puzzles(&book, &table);
+    //Deterministic so the accuracy can be compared between runs
+    play_all_puzzles(&book, &table, &BBSettings { deterministic: true, ..bb_settings::STANDARD_SETTINGS });
 
 
     match_handler::play_game_player(&mut Game::get_start_position(), 
@@ -92,10 +131,18 @@ fn main() {
     println!("Done");
 }
 
+//Missing files are skipped, the engine then plays without endgame table or opening book
 fn load_files() -> (EndgameTable, OpeningBook) {
     let table = EndgameTable::load(4);
 
-    let book = OpeningBook::load_from_file("C:\\Users\\hmart\\Documents\\GitHub\\Chess-Challenge\\Rust\\barschbot\\book.txt");
+    let book_path = "C:\\Users\\hmart\\Documents\\GitHub\\Chess-Challenge\\Rust\\barschbot\\book.txt";
+    let book = if Path::new(book_path).exists() {
+        OpeningBook::load_from_file(book_path)
+    }
+    else {
+        eprintln!("No opening book at {}, playing without it", book_path);
+        OpeningBook::new()
+    };
 
     return (table, book);
 }
@@ -140,7 +187,8 @@ fn load_lichess_puzzles() -> Vec<(String, Vec<ChessMove>)>{
 //Depth 2: 2579431 / 3678110 (70.12925%)
 //Depth 3: 2835433 / 3678110 (77.0894%)
 
-fn play_all_puzzles(book: &OpeningBook, table: &EndgameTable ) {
+//Run with different settings, e.g. single extensions switched off, to compare their tactical strength
+fn play_all_puzzles(book: &OpeningBook, table: &EndgameTable, settings: &BBSettings) {
     let mut puzzles = load_lichess_puzzles();
 
 
@@ -154,9 +202,11 @@ fn play_all_puzzles(book: &OpeningBook, table: &EndgameTable ) {
     puzzles.par_chunks_mut(counter / THREAD_COUNT).for_each(|slice| {
         let mut correct = 0;
         let mut counter = 0;
+        let mut tt = TranspositionTable::new(transposition_table::DEFAULT_SIZE_MB);
         for (fen, moves) in slice {
             counter += 1;
             let mut game = Game::from_fen(&fen);
+            tt.clear();
             let mut all_correct = true;
             
             for i in 0..moves.len() {
@@ -171,7 +221,7 @@ fn play_all_puzzles(book: &OpeningBook, table: &EndgameTable ) {
                     //let ml = game.get_legal_moves();
                     //let bmove = ml[rng.gen_range(0..ml.len())];
     
-                    let bmove = barsch_bot::get_best_move(&mut game, table, &bb_settings::STANDARD_SETTINGS, book);
+                    let bmove = barsch_bot::get_best_move(&mut game, table, settings, book, &mut tt);
                     
                     //println!("Expected: {} Barsch: {}", moves[i].get_uci(), bmove.get_uci());
                     
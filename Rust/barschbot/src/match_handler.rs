@@ -1,6 +1,6 @@
-use std::{process::{Child, Stdio, Command}, io::{BufWriter, BufReader, Write, BufRead, self}, time::{Duration, Instant}};
+use std::{process::{Child, Stdio, Command}, io::{BufWriter, BufReader, Write, BufRead, self}, time::{Duration, Instant}, sync::{atomic::{AtomicBool, Ordering}, Mutex}, thread::{self, ScopedJoinHandle}};
 
-use crate::{visualizer::Visualizer, square::{Square, self}, game::{Game, GameState}, chess_move::{ChessMove, self}, endgame_table::EndgameTable, opening_book::OpeningBook, bb_settings::BBSettings, barsch_bot};
+use crate::{visualizer::Visualizer, square::{Square, self}, game::{Game, GameState}, chess_move::{ChessMove, self}, endgame_table::EndgameTable, opening_book::OpeningBook, bb_settings::{self, BBSettings}, barsch_bot::{self, SearchInfo, SearchSignals}, time_manager::TimeControl, transposition_table::{self, TranspositionTable}};
 
 
 pub fn get_human_move(app: &mut Visualizer, game: &mut Game) -> ChessMove {
@@ -90,12 +90,13 @@ fn create_process_and_get_sf_move(game: &mut Game) -> ChessMove {
     return get_stock_fish_move(game, &mut cmd);
 }
 
-fn get_barschbot_move(game: &mut Game, table: &EndgameTable, settings: &BBSettings, book: &OpeningBook) -> ChessMove {
-    return barsch_bot::get_best_move(game, table, settings, book);
+fn get_barschbot_move(game: &mut Game, table: &EndgameTable, settings: &BBSettings, book: &OpeningBook, tt: &mut TranspositionTable) -> ChessMove {
+    return barsch_bot::get_best_move(game, table, settings, book, tt);
 }
 
 pub fn play_game_player(game: &mut Game, mut human_turn: bool, settings: &BBSettings, table: &EndgameTable, book: &OpeningBook) { 
     let mut app = Visualizer::new();
+    let mut tt = TranspositionTable::new(transposition_table::DEFAULT_SIZE_MB);
     let flip = false;
 
     app.render_board(&game.get_board().type_field, chess_move::NULL_MOVE, flip);    
@@ -106,11 +107,11 @@ pub fn play_game_player(game: &mut Game, mut human_turn: bool, settings: &BBSett
             get_human_move(&mut app, game)
         }
         else {
-            get_barschbot_move(game, table, settings, book)
+            get_barschbot_move(game, table, settings, book, &mut tt)
         };
 
         if cm == chess_move::NULL_MOVE {
-            cm = get_barschbot_move(game, table, settings, book);
+            cm = get_barschbot_move(game, table, settings, book, &mut tt);
         }
 
         human_turn = !human_turn;
@@ -128,11 +129,13 @@ pub fn play_bot_game(game: &mut Game, table: &EndgameTable, book: &OpeningBook,
     let mut first_player = true;
     let mut duration_1 = Duration::ZERO;
     let mut duration_2 = Duration::ZERO;
+    let mut tt_a = TranspositionTable::new(transposition_table::DEFAULT_SIZE_MB);
+    let mut tt_b = TranspositionTable::new(transposition_table::DEFAULT_SIZE_MB);
 
     while game.get_game_state() == GameState::Undecided {
-        let set = if first_player { bb_settings_a } else { bb_settings_b };
+        let (set, tt) = if first_player { (bb_settings_a, &mut tt_a) } else { (bb_settings_b, &mut tt_b) };
         let start = Instant::now();
-        let cm = barsch_bot::get_best_move(game, table, set, book);
+        let cm = barsch_bot::get_best_move(game, table, set, book, tt);
         
         if first_player {
             duration_1 += start.elapsed();
@@ -152,12 +155,13 @@ pub fn play_bot_game(game: &mut Game, table: &EndgameTable, book: &OpeningBook,
 pub fn barsch_vs_sf(game: &mut Game, bb_setting: &BBSettings, book: &OpeningBook, table: &EndgameTable, mut barsch_turn: bool, cmd: &mut Child) -> (GameState, Duration, Duration) {
     let mut duration_1 = Duration::ZERO;
     let mut duration_2 = Duration::ZERO;
+    let mut tt = TranspositionTable::new(transposition_table::DEFAULT_SIZE_MB);
 
     while game.get_game_state() == GameState::Undecided {
         
         let start = Instant::now();
         let cm = if barsch_turn {
-            barsch_bot::get_best_move(game, table, bb_setting, book)
+            barsch_bot::get_best_move(game, table, bb_setting, book, &mut tt)
         } else {
             get_stock_fish_move(game, cmd)
         };
@@ -177,10 +181,232 @@ pub fn barsch_vs_sf(game: &mut Game, bb_setting: &BBSettings, book: &OpeningBook
     return (game.get_game_state(), duration_1, duration_2);
 }
 
-fn uci_loop() {
-    let mut buffer = String::new();
-    let stdin = io::stdin(); // We get `Stdin` here.
-    stdin.read_line(&mut buffer);
+pub fn uci_loop(table: &EndgameTable, book: &OpeningBook) {
+    let mut game = Game::get_start_position();
+    let signals = SearchSignals::new();
+    //Locked by the search thread while it runs, so it is only touched after stop_search
+    let tt = Mutex::new(TranspositionTable::new(transposition_table::DEFAULT_SIZE_MB));
+    //Settings changed by setoption, every go starts from these
+    let mut options = bb_settings::STANDARD_SETTINGS;
+    //Set by "debug on", adds the principal variation in SAN to the info output
+    let mut debug = false;
+    let stdin = io::stdin();
+
+    //Scoped so the search thread can borrow the endgame table and the opening book
+    thread::scope(|scope| {
+        let mut search: Option<ScopedJoinHandle<()>> = None;
+
+        for line in stdin.lock().lines() {
+            let line = line.unwrap();
+            let tokens = line.split_whitespace().collect::<Vec<_>>();
+
+            if tokens.is_empty() {
+                continue;
+            }
+
+            match tokens[0] {
+                "uci" => {
+                    println!("id name BarschBot");
+                    println!("id author Bobitsmagic");
+                    println!("option name Hash type spin default {} min 1 max {}", transposition_table::DEFAULT_SIZE_MB, transposition_table::MAX_SIZE_MB);
+                    println!("option name Threads type spin default 1 min 1 max {}", barsch_bot::MAX_THREADS);
+                    println!("option name MultiPV type spin default 1 min 1 max {}", barsch_bot::MAX_MULTI_PV);
+                    println!("option name Deterministic type check default false");
+                    println!("option name Ponder type check default false");
+                    println!("uciok");
+                },
+                "isready" => println!("readyok"),
+                "debug" => debug = tokens.get(1) == Some(&"on"),
+                "ucinewgame" => {
+                    stop_search(&signals, &mut search);
+                    game = Game::get_start_position();
+                    tt.lock().unwrap().clear();
+                },
+                "setoption" => {
+                    stop_search(&signals, &mut search);
+                    set_uci_option(&tokens, &mut tt.lock().unwrap(), &mut options);
+                },
+                "position" => {
+                    stop_search(&signals, &mut search);
+                    game = parse_uci_position(&tokens);
+                },
+                "go" => {
+                    stop_search(&signals, &mut search);
+                    let (settings, time_control) = parse_uci_go(&tokens, &options);
+                    let mut search_game = game.clone();
+                    let signals = &signals;
+                    let tt = &tt;
+
+                    signals.stop.store(false, Ordering::Relaxed);
+                    signals.ponder.store(time_control.ponder, Ordering::Relaxed);
+
+                    search = Some(scope.spawn(move || {
+                        if search_game.get_game_state() != GameState::Undecided {
+                            search_game = Game::from_board(search_game.get_board());
+                        }
+
+                        let mut tt = tt.lock().unwrap();
+                        let line = barsch_bot::search(&mut search_game, table, &settings, book, &mut tt, &time_control, signals, &mut |info| print_uci_info(info, debug));
+
+                        //The GUI expects the best move of an infinite or ponder search only after it sent stop or ponderhit
+                        while (time_control.infinite || signals.ponder.load(Ordering::Relaxed)) && !signals.stop.load(Ordering::Relaxed) {
+                            thread::sleep(Duration::from_millis(1));
+                        }
+
+                        //The expected reply is what the GUI lets us ponder on
+                        match line.pv.get(1) {
+                            Some(reply) => println!("bestmove {} ponder {}", get_uci_move_name(line.chess_move), get_uci_move_name(*reply)),
+                            None => println!("bestmove {}", get_uci_move_name(line.chess_move)),
+                        }
+                    }));
+                },
+                "ponderhit" => signals.ponder.store(false, Ordering::Relaxed),
+                "stop" => stop_search(&signals, &mut search),
+                "quit" => break,
+                _ => println!("info string Unknown command: {}", line),
+            }
+        }
+
+        stop_search(&signals, &mut search);
+    });
+}
+
+fn stop_search(signals: &SearchSignals, search: &mut Option<ScopedJoinHandle<()>>) {
+    if let Some(handle) = search.take() {
+        signals.stop.store(true, Ordering::Relaxed);
+        handle.join().unwrap();
+    }
+}
+
+fn set_uci_option(tokens: &[&str], tt: &mut TranspositionTable, options: &mut BBSettings) {
+    let name_index = tokens.iter().position(|t| *t == "name");
+    let value_index = tokens.iter().position(|t| *t == "value");
+
+    let (name, value) = match (name_index, value_index) {
+        (Some(n), Some(v)) if n < v => (tokens[(n + 1)..v].join(" "), tokens[(v + 1)..].join(" ")),
+        _ => {
+            println!("info string Malformed setoption");
+            return;
+        }
+    };
+
+    match name.to_lowercase().as_str() {
+        "hash" => match value.parse::<usize>() {
+            Ok(size_mb) => tt.resize(size_mb),
+            Err(_) => println!("info string Invalid hash size: {}", value),
+        },
+        "threads" => match value.parse::<u8>() {
+            Ok(count) => options.threads = count.clamp(1, barsch_bot::MAX_THREADS),
+            Err(_) => println!("info string Invalid thread count: {}", value),
+        },
+        "multipv" => match value.parse::<u8>() {
+            Ok(count) => options.multi_pv = count.clamp(1, barsch_bot::MAX_MULTI_PV),
+            Err(_) => println!("info string Invalid MultiPV count: {}", value),
+        },
+        //Pondering is controlled by the GUI through go ponder
+        "ponder" => (),
+        "deterministic" => match value.parse::<bool>() {
+            Ok(deterministic) => options.deterministic = deterministic,
+            Err(_) => println!("info string Invalid check value: {}", value),
+        },
+        _ => println!("info string Unknown option: {}", name),
+    }
+}
+
+fn print_uci_info(info: &SearchInfo, debug: bool) {
+    let pv = info.pv.iter().map(|cm| cm.get_uci()).collect::<Vec<_>>().join(" ");
+
+    println!("info depth {} multipv {} score {} nodes {} nps {} hashfull {} time {} pv {}", info.depth, info.multi_pv, info.score.to_uci(), info.nodes, info.nps(), info.hashfull, info.time_ms, pv);
+
+    if debug {
+        println!("info string pv san {}", info.san_pv);
+    }
+}
+
+fn parse_uci_position(tokens: &[&str]) -> Game {
+    let moves_index = tokens.iter().position(|t| *t == "moves").unwrap_or(tokens.len());
+
+    let mut game = if tokens.len() > 1 && tokens[1] == "fen" {
+        Game::from_fen(&tokens[2..moves_index].join(" "))
+    }
+    else {
+        Game::get_start_position()
+    };
+
+    for uci in tokens.iter().skip(moves_index + 1) {
+        //Game treats the first repetition as a draw, so the history is restarted to let the GUI continue
+        if game.get_game_state() != GameState::Undecided {
+            game = Game::from_board(game.get_board());
+        }
 
-    println!("Reading: [{}]", buffer);
-}
\ No newline at end of file
+        let cm = game.get_uci_move(uci.to_string());
+
+        if cm == chess_move::NULL_MOVE {
+            println!("info string Illegal move: {}", uci);
+            break;
+        }
+
+        game.make_move(cm);
+    }
+
+    return game;
+}
+
+fn parse_uci_go(tokens: &[&str], options: &BBSettings) -> (BBSettings, TimeControl) {
+    let mut settings = options.clone();
+    let mut time_control = TimeControl::none();
+    let mut depth_given = false;
+
+    let mut i = 1;
+    while i < tokens.len() {
+        //Some GUIs send negative clocks once the time is used up
+        let value = tokens.get(i + 1).and_then(|t| t.parse::<i64>().ok()).map(|v| v.max(0) as u64);
+
+        match tokens[i] {
+            "depth" => {
+                if let Some(d) = value {
+                    settings.max_depth = d.clamp(1, barsch_bot::MAX_SEARCH_DEPTH as u64) as u8;
+                    depth_given = true;
+                }
+            },
+            "wtime" => time_control.wtime = value,
+            "btime" => time_control.btime = value,
+            "winc" => time_control.winc = value.unwrap_or(0),
+            "binc" => time_control.binc = value.unwrap_or(0),
+            "movestogo" => time_control.movestogo = value,
+            "movetime" => time_control.movetime = value,
+            "nodes" => time_control.nodes = value,
+            "infinite" => {
+                time_control.infinite = true;
+                i += 1;
+                continue;
+            },
+            "ponder" => {
+                time_control.ponder = true;
+                i += 1;
+                continue;
+            },
+            _ => {
+                i += 1;
+                continue;
+            }
+        }
+
+        i += 2;
+    }
+
+    //With a clock or a node budget the time manager decides when to stop
+    if (time_control.is_timed() || time_control.nodes.is_some() || time_control.infinite) && !depth_given {
+        settings.max_depth = barsch_bot::MAX_SEARCH_DEPTH;
+    }
+
+    return (settings, time_control);
+}
+
+fn get_uci_move_name(cm: ChessMove) -> String {
+    if cm.is_null_move() {
+        return "0000".to_owned();
+    }
+
+    return cm.get_uci();
+}
@@ -0,0 +1,103 @@
+use crate::chess_move::{ChessMove, NULL_MOVE};
+
+//Plies with their own killer slots, deeper nodes share none
+pub const MAX_PLY: usize = 128;
+//History values stay within -HISTORY_MAX..HISTORY_MAX
+const HISTORY_MAX: i32 = 1 << 14;
+
+//Quiet move scores, every killer and countermove is placed before any history score
+const KILLER_SCORE: i32 = 4 * HISTORY_MAX;
+const COUNTERMOVE_SCORE: i32 = 2 * HISTORY_MAX;
+
+//Quiet move statistics collected from beta cutoffs during one search
+pub struct MoveOrdering {
+    killers: [[ChessMove; 2]; MAX_PLY],
+    //Indexed by side to move, start and target square
+    history: [[[i32; 64]; 64]; 2],
+    //Indexed by piece and target square of the previous move
+    countermoves: [[ChessMove; 64]; 13],
+}
+
+impl MoveOrdering {
+    pub fn new() -> MoveOrdering {
+        return MoveOrdering { killers: [[NULL_MOVE; 2]; MAX_PLY], history: [[[0; 64]; 64]; 2], countermoves: [[NULL_MOVE; 64]; 13] };
+    }
+
+    pub fn is_quiet(m: ChessMove) -> bool {
+        return !(m.is_capture() || m.is_promotion());
+    }
+
+    fn get_history_mut(&mut self, m: ChessMove) -> &mut i32 {
+        let color = if m.move_piece_type.is_white() { 0 } else { 1 };
+
+        return &mut self.history[color][m.start_square as usize][m.target_square as usize];
+    }
+
+    pub fn get_history(&self, m: ChessMove) -> i32 {
+        let color = if m.move_piece_type.is_white() { 0 } else { 1 };
+
+        return self.history[color][m.start_square as usize][m.target_square as usize];
+    }
+
+    pub fn get_countermove(&self, prev_move: ChessMove) -> ChessMove {
+        if prev_move.is_null_move() {
+            return NULL_MOVE;
+        }
+
+        return self.countermoves[prev_move.move_piece_type as usize][prev_move.target_square as usize];
+    }
+
+    pub fn get_killers(&self, ply: u8) -> [ChessMove; 2] {
+        if ply as usize >= MAX_PLY {
+            return [NULL_MOVE; 2];
+        }
+
+        return self.killers[ply as usize];
+    }
+
+    //Higher is better, only meaningful for quiet moves
+    pub fn get_quiet_score(&self, m: ChessMove, ply: u8, prev_move: ChessMove) -> i32 {
+        let killers = self.get_killers(ply);
+
+        if m == killers[0] {
+            return KILLER_SCORE + 1;
+        }
+
+        if m == killers[1] {
+            return KILLER_SCORE;
+        }
+
+        if m == self.get_countermove(prev_move) {
+            return COUNTERMOVE_SCORE;
+        }
+
+        return self.get_history(m);
+    }
+
+    //The history gravity keeps values bounded and lets old cutoffs fade out
+    fn update_history(&mut self, m: ChessMove, bonus: i32) {
+        let entry = self.get_history_mut(m);
+
+        *entry += bonus - *entry * bonus.abs() / HISTORY_MAX;
+    }
+
+    //Rewards the quiet move that failed high and punishes the quiet moves searched before it
+    pub fn add_cutoff(&mut self, m: ChessMove, ply: u8, depth_left: u8, prev_move: ChessMove, searched_quiets: &[ChessMove]) {
+        if (ply as usize) < MAX_PLY && self.killers[ply as usize][0] != m {
+            self.killers[ply as usize][1] = self.killers[ply as usize][0];
+            self.killers[ply as usize][0] = m;
+        }
+
+        let bonus = (depth_left as i32 * depth_left as i32).min(HISTORY_MAX);
+
+        self.update_history(m, bonus);
+
+        for q in searched_quiets {
+            self.update_history(*q, -bonus);
+        }
+
+        if !prev_move.is_null_move() {
+            self.countermoves[prev_move.move_piece_type as usize][prev_move.target_square as usize] = m;
+        }
+    }
+}
@@ -0,0 +1,220 @@
+use arrayvec::ArrayVec;
+
+use crate::{barsch_bot, bit_board::BitBoard, chess_move::{ChessMove, NULL_MOVE}, move_ordering::MoveOrdering, piece_type::PieceType};
+
+//Promotion and threat values in tenths of a pawn, the same as in barsch_bot::better_move_sorter
+const PIECE_VALUES: [i32; 7] = [10, 28, 32, 50, 90, 100, 0];
+
+#[derive(Clone, Copy, PartialEq)]
+enum Stage {
+    HashMove, GenerateCaptures, GoodCaptures, Killers, GenerateQuiets, Quiets, BadCaptures, Evasions, Done
+}
+
+//Hands out the moves of a node best first, the next kind of moves is only generated once the previous ones are used up
+//Order: hash move, captures that do not lose material, killers, quiet moves by history, losing captures
+//In check every evasion is generated and sorted at once, there are only a few of them
+pub struct MovePicker {
+    board: BitBoard,
+    stage: Stage,
+    hash_move: ChessMove,
+    killers: [ChessMove; 2],
+    ply: u8,
+    prev_move: ChessMove,
+
+    //Moves of the current stage and their scores, best first
+    moves: ArrayVec<(ChessMove, i32), 200>,
+    index: usize,
+    bad_captures: ArrayVec<(ChessMove, i32), 200>,
+    evasion_count: Option<usize>,
+}
+
+impl MovePicker {
+    pub fn new(board: &BitBoard, hash_move: ChessMove, ordering: &MoveOrdering, ply: u8, prev_move: ChessMove) -> MovePicker {
+        let mut picker = MovePicker { board: *board, stage: Stage::HashMove, hash_move, killers: ordering.get_killers(ply), ply, prev_move,
+            moves: ArrayVec::new(), index: 0, bad_captures: ArrayVec::new(), evasion_count: None };
+
+        if board.in_check() {
+            let mut list = board.get_legal_moves();
+            barsch_bot::better_move_sorter(&mut list, board, hash_move, ordering, ply, prev_move);
+
+            picker.evasion_count = Some(list.len());
+            picker.moves = list.into_iter().map(|m| (m, 0)).collect();
+            picker.stage = Stage::Evasions;
+        }
+
+        return picker;
+    }
+
+    //Number of legal moves, only known in check
+    pub fn get_evasion_count(&self) -> Option<usize> {
+        return self.evasion_count;
+    }
+
+    pub fn next(&mut self, ordering: &MoveOrdering) -> Option<ChessMove> {
+        loop {
+            match self.stage {
+                Stage::HashMove => {
+                    self.stage = Stage::GenerateCaptures;
+
+                    //The hash move can come from another position sharing the entry
+                    if self.board.is_legal_move(self.hash_move) {
+                        return Some(self.hash_move);
+                    }
+                },
+                Stage::GenerateCaptures => {
+                    for m in self.board.get_legal_captures() {
+                        if m == self.hash_move {
+                            continue;
+                        }
+
+                        let see = if m.is_capture() { self.board.see(m) } else { 0 };
+
+                        //Captures that lose material are tried after all quiet moves
+                        if see < 0 {
+                            self.bad_captures.push((m, see));
+                            continue;
+                        }
+
+                        let mut score = if m.is_capture() { see / 10 + 200 } else { 0 };
+                        score = score * 1000 + PIECE_VALUES[PieceType::from_cpt(m.promotion_piece_type) as usize];
+                        score = score * 1000 + self.get_threat_score(m);
+
+                        self.moves.push((m, score));
+                    }
+
+                    self.start_stage(Stage::GoodCaptures);
+                },
+                Stage::GoodCaptures => {
+                    if let Some(m) = self.next_in_stage() {
+                        return Some(m);
+                    }
+
+                    self.start_stage(Stage::Killers);
+                },
+                Stage::Killers => {
+                    //The index counts the killers already tried
+                    while self.index < self.killers.len() {
+                        let killer = self.killers[self.index];
+                        self.index += 1;
+
+                        if killer != self.hash_move && MoveOrdering::is_quiet(killer) && self.board.is_legal_move(killer) {
+                            return Some(killer);
+                        }
+                    }
+
+                    self.stage = Stage::GenerateQuiets;
+                },
+                Stage::GenerateQuiets => {
+                    for m in self.board.get_legal_quiets() {
+                        if m == self.hash_move || self.killers.contains(&m) {
+                            continue;
+                        }
+
+                        let score = ordering.get_quiet_score(m, self.ply, self.prev_move) * 1000 + self.get_threat_score(m);
+
+                        self.moves.push((m, score));
+                    }
+
+                    self.start_stage(Stage::Quiets);
+                },
+                Stage::Quiets => {
+                    if let Some(m) = self.next_in_stage() {
+                        return Some(m);
+                    }
+
+                    self.moves = std::mem::take(&mut self.bad_captures);
+                    self.start_stage(Stage::BadCaptures);
+                },
+                Stage::BadCaptures | Stage::Evasions => {
+                    if let Some(m) = self.next_in_stage() {
+                        return Some(m);
+                    }
+
+                    self.stage = Stage::Done;
+                },
+                Stage::Done => {
+                    return None;
+                },
+            }
+        }
+    }
+
+    //Sorts the moves of the new stage, equal scores keep the generation order
+    fn start_stage(&mut self, stage: Stage) {
+        self.moves.sort_by_key(|(_, score)| -score);
+        self.index = 0;
+        self.stage = stage;
+    }
+
+    fn next_in_stage(&mut self) -> Option<ChessMove> {
+        if self.index < self.moves.len() {
+            self.index += 1;
+            return Some(self.moves[self.index - 1].0);
+        }
+
+        self.moves.clear();
+
+        return None;
+    }
+
+    //Pieces the moved piece attacks from its target square
+    fn get_threat_score(&self, m: ChessMove) -> i32 {
+        return self.board.get_piece_captures_at(m.move_piece_type, m.target_square).iter()
+            .map(|x| PIECE_VALUES[*x as usize]).sum::<i32>();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pick_all(fen: &str, hash_move: &str) -> Vec<ChessMove> {
+        let board = BitBoard::from_fen(fen);
+        let ordering = MoveOrdering::new();
+        let hash_move = board.get_legal_moves().into_iter().find(|m| m.get_uci() == hash_move).unwrap_or(NULL_MOVE);
+
+        let mut picker = MovePicker::new(&board, hash_move, &ordering, 0, NULL_MOVE);
+        let mut picked = Vec::new();
+
+        while let Some(m) = picker.next(&ordering) {
+            picked.push(m);
+        }
+
+        return picked;
+    }
+
+    #[test]
+    fn picks_every_legal_move_once() {
+        let positions = [
+            ("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1", "e1g1"),
+            ("rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3", "e5f6"),
+            ("n1n5/PPPk4/8/8/8/8/4Kppp/5N1N b - - 0 1", "g2g1q"),
+            ("r1bqkbnr/pppp1ppp/2n5/4p2Q/2B1P3/8/PPPP1PPP/RNB1K1NR b KQkq - 3 3", "d7d6"),
+            ("4k3/8/8/8/8/8/4r3/R3K3 w Q - 0 1", "e1d1"),
+        ];
+
+        for (fen, hash_move) in positions {
+            let board = BitBoard::from_fen(fen);
+            let mut picked = pick_all(fen, hash_move);
+            let mut legal = board.get_legal_moves().to_vec();
+
+            if !board.in_check() {
+                assert!(picked[0].get_uci() == hash_move, "{}", fen);
+            }
+
+            picked.sort_by_key(|m| (m.get_uci(), m.move_piece_type as u8));
+            legal.sort_by_key(|m| (m.get_uci(), m.move_piece_type as u8));
+
+            assert!(picked == legal, "{}", fen);
+        }
+    }
+
+    #[test]
+    fn losing_captures_come_last() {
+        //Qxa4 wins a pawn, Qxd5 loses the queen to the pawn on e6
+        let picked = pick_all("4k3/8/4p3/3p4/p7/8/8/3QK3 w - - 0 1", "");
+
+        assert_eq!(picked.first().unwrap().get_uci(), "d1a4");
+        assert_eq!(picked.last().unwrap().get_uci(), "d1d5");
+    }
+}
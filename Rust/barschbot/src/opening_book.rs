@@ -1,9 +1,9 @@
 use std::{collections::HashMap, fs::{File, read_to_string}, io::BufReader};
 
-use crate::{chess_move::{ChessMove, NULL_MOVE}, bit_board::BitBoard};
+use crate::{chess_move::{ChessMove, PackedMove, NULL_MOVE}, bit_board::BitBoard};
 
 pub struct OpeningBook {
-    moves: HashMap<u64, ChessMove>,
+    moves: HashMap<u64, PackedMove>,
 }
 
 impl OpeningBook {
@@ -14,7 +14,7 @@ impl OpeningBook {
     }
 
     pub fn load_from_file(path: &str) -> OpeningBook {
-        let mut moves: HashMap<u64, ChessMove> = HashMap::new();
+        let mut moves: HashMap<u64, PackedMove> = HashMap::new();
 
         for line in read_to_string(path).unwrap().lines() {
             let parts = line.split(",").collect::<Vec<_>>();
@@ -29,7 +29,7 @@ impl OpeningBook {
             //board.print();
             //println!("Book move {}", m.get_board_name(&board));
 
-            moves.insert(hash, *m);
+            moves.insert(hash, m.pack());
         }
 
         return OpeningBook {
@@ -37,9 +37,9 @@ impl OpeningBook {
         }
     }
 
-    pub fn get_move(&self, hash: u64) -> ChessMove {
-        if self.moves.contains_key(&hash) {
-            return self.moves.get(&hash).unwrap().clone();
+    pub fn get_move(&self, board: &BitBoard) -> ChessMove {
+        if let Some(m) = self.moves.get(&board.get_zoberist_hash()) {
+            return m.unpack(board);
         }
 
         return NULL_MOVE;
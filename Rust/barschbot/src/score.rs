@@ -0,0 +1,134 @@
+use std::ops::{Add, Neg, Sub};
+
+//Being mated right now, mate scores count the plies from the root towards zero
+const MATE_VALUE: i32 = 30000;
+//Mate scores lie within MAX_MATE_PLY of MATE_VALUE
+const MAX_MATE_PLY: i32 = 1000;
+//Static evaluations are kept out of the mate range
+const MAX_EVAL: i32 = 20000;
+
+//Centipawns from the side to move's perspective, or a mate distance in plies
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub struct Score(i32);
+
+impl Score {
+    //Bound for search windows, larger than any real score
+    pub const INFINITY: Score = Score(MATE_VALUE + 1);
+    pub const DRAW: Score = Score(0);
+
+    pub fn from_centipawns(value: i32) -> Score {
+        return Score(value.clamp(-MAX_EVAL, MAX_EVAL));
+    }
+
+    pub fn from_pawns(value: f32) -> Score {
+        return Score::from_centipawns((value * 100.0).round() as i32);
+    }
+
+    //The side to move delivers mate at the given ply from the root
+    pub fn mate_in(ply: u32) -> Score {
+        return Score(MATE_VALUE - ply as i32);
+    }
+
+    //The side to move gets mated at the given ply from the root
+    pub fn mated_in(ply: u32) -> Score {
+        return Score(-MATE_VALUE + ply as i32);
+    }
+
+    //Inverse of get_value, for scores that were stored compactly
+    pub fn from_raw(value: i32) -> Score {
+        return Score(value);
+    }
+
+    pub fn get_value(&self) -> i32 {
+        return self.0;
+    }
+
+    pub fn as_pawns(&self) -> f32 {
+        return self.0 as f32 / 100.0;
+    }
+
+    pub fn is_mate(&self) -> bool {
+        return self.0.abs() >= MATE_VALUE - MAX_MATE_PLY;
+    }
+
+    //Plies from the root until mate, positive if the side to move is mating
+    pub fn get_mate_plies(&self) -> Option<i32> {
+        if !self.is_mate() {
+            return None;
+        }
+
+        return Some(if self.0 > 0 { MATE_VALUE - self.0 } else { -(MATE_VALUE + self.0) });
+    }
+
+    //The transposition table stores mate scores relative to the node instead of the root
+    pub fn to_tt(self, ply: u8) -> Score {
+        if !self.is_mate() || self == Score::INFINITY || self == -Score::INFINITY {
+            return self;
+        }
+
+        return if self.0 > 0 { Score(self.0 + ply as i32) } else { Score(self.0 - ply as i32) };
+    }
+
+    pub fn tt_to_search(self, ply: u8) -> Score {
+        if !self.is_mate() || self == Score::INFINITY || self == -Score::INFINITY {
+            return self;
+        }
+
+        return if self.0 > 0 { Score(self.0 - ply as i32) } else { Score(self.0 + ply as i32) };
+    }
+
+    //"cp X" or "mate N" with N in moves as used by the UCI info command
+    pub fn to_uci(self) -> String {
+        return match self.get_mate_plies() {
+            Some(plies) if plies > 0 => format!("mate {}", (plies + 1) / 2),
+            Some(plies) => format!("mate {}", -((-plies + 1) / 2)),
+            None => format!("cp {}", self.0),
+        };
+    }
+}
+
+impl Neg for Score {
+    type Output = Score;
+
+    fn neg(self) -> Score {
+        return Score(-self.0);
+    }
+}
+
+impl Add<i32> for Score {
+    type Output = Score;
+
+    fn add(self, centipawns: i32) -> Score {
+        return Score(self.0 + centipawns);
+    }
+}
+
+impl Sub<i32> for Score {
+    type Output = Score;
+
+    fn sub(self, centipawns: i32) -> Score {
+        return Score(self.0 - centipawns);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mate_scores_survive_the_transposition_table() {
+        let score = Score::mate_in(7);
+
+        assert_eq!(score.to_tt(3).tt_to_search(3), score);
+        assert_eq!(score.to_tt(3).tt_to_search(5), Score::mate_in(9));
+        assert_eq!(Score::from_centipawns(250).to_tt(4), Score::from_centipawns(250));
+    }
+
+    #[test]
+    fn uci_output() {
+        assert_eq!(Score::mate_in(1).to_uci(), "mate 1");
+        assert_eq!(Score::mate_in(3).to_uci(), "mate 2");
+        assert_eq!(Score::mated_in(2).to_uci(), "mate -1");
+        assert_eq!(Score::from_pawns(-1.5).to_uci(), "cp -150");
+    }
+}
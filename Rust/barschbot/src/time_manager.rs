@@ -0,0 +1,127 @@
+use std::time::Instant;
+
+//Milliseconds kept back for GUI and process overhead
+const MOVE_OVERHEAD: u64 = 30;
+//Assumed number of moves left when the GUI does not send movestogo
+const DEFAULT_MOVES_TO_GO: u64 = 30;
+//The hard limit may use this many times the soft limit
+const HARD_LIMIT_FACTOR: u64 = 4;
+//Never plan to use more than this fraction of the remaining clock on one move
+const MAX_CLOCK_DIVISOR: u64 = 2;
+
+//All times in milliseconds, as sent with the UCI "go" command
+#[derive(Clone, Copy, Default)]
+pub struct TimeControl {
+    pub wtime: Option<u64>,
+    pub btime: Option<u64>,
+    pub winc: u64,
+    pub binc: u64,
+    pub movestogo: Option<u64>,
+    pub movetime: Option<u64>,
+    //Search at most this many nodes, "go nodes"
+    pub nodes: Option<u64>,
+    //Search until stopped, "go infinite"
+    pub infinite: bool,
+    //Search the expected reply while the opponent thinks, "go ponder"
+    pub ponder: bool,
+}
+
+impl TimeControl {
+    pub fn none() -> TimeControl {
+        return TimeControl::default();
+    }
+
+    pub fn is_timed(&self) -> bool {
+        return self.wtime.is_some() || self.btime.is_some() || self.movetime.is_some();
+    }
+}
+
+pub struct TimeManager {
+    start: Instant,
+    //No new iteration is started after this
+    soft_limit: Option<u128>,
+    //The running iteration is aborted after this
+    hard_limit: Option<u128>,
+    //Unlike the clock this limit is reproducible
+    node_limit: Option<u64>,
+}
+
+impl TimeManager {
+    pub fn infinite() -> TimeManager {
+        return TimeManager { start: Instant::now(), soft_limit: None, hard_limit: None, node_limit: None };
+    }
+
+    pub fn new(time_control: &TimeControl, whites_turn: bool) -> TimeManager {
+        if time_control.infinite {
+            return TimeManager::infinite();
+        }
+
+        let mut time_manager = TimeManager::from_clock(time_control, whites_turn);
+        time_manager.node_limit = time_control.nodes;
+
+        return time_manager;
+    }
+
+    fn from_clock(time_control: &TimeControl, whites_turn: bool) -> TimeManager {
+        if let Some(movetime) = time_control.movetime {
+            let limit = movetime.saturating_sub(MOVE_OVERHEAD).max(1) as u128;
+
+            return TimeManager { start: Instant::now(), soft_limit: Some(limit), hard_limit: Some(limit), node_limit: None };
+        }
+
+        let clock = if whites_turn { time_control.wtime } else { time_control.btime };
+        let increment = if whites_turn { time_control.winc } else { time_control.binc };
+
+        let time_left = match clock {
+            Some(t) => t.saturating_sub(MOVE_OVERHEAD).max(1),
+            None => return TimeManager::infinite(),
+        };
+
+        let moves_to_go = time_control.movestogo.unwrap_or(DEFAULT_MOVES_TO_GO).max(1);
+
+        let hard_cap = (time_left / MAX_CLOCK_DIVISOR).max(1);
+        let soft_limit = (time_left / moves_to_go + increment * 3 / 4).min(hard_cap);
+        let hard_limit = (soft_limit * HARD_LIMIT_FACTOR).min(hard_cap);
+
+        return TimeManager { start: Instant::now(), soft_limit: Some(soft_limit as u128), hard_limit: Some(hard_limit as u128), node_limit: None };
+    }
+
+    //Keeps only the node limit so the search does not depend on the speed of the machine
+    pub fn without_clock(self) -> TimeManager {
+        return TimeManager { soft_limit: None, hard_limit: None, ..self };
+    }
+
+    //Time spent pondering does not count, the limits apply from the ponderhit on
+    pub fn restart(&mut self) {
+        self.start = Instant::now();
+    }
+
+    pub fn is_timed(&self) -> bool {
+        return self.soft_limit.is_some();
+    }
+
+    pub fn elapsed_ms(&self) -> u128 {
+        return self.start.elapsed().as_millis();
+    }
+
+    pub fn soft_limit_reached(&self) -> bool {
+        return match self.soft_limit {
+            Some(limit) => self.elapsed_ms() >= limit,
+            None => false,
+        };
+    }
+
+    pub fn node_limit_reached(&self, nodes: u64) -> bool {
+        return match self.node_limit {
+            Some(limit) => nodes >= limit,
+            None => false,
+        };
+    }
+
+    pub fn hard_limit_reached(&self) -> bool {
+        return match self.hard_limit {
+            Some(limit) => self.elapsed_ms() >= limit,
+            None => false,
+        };
+    }
+}
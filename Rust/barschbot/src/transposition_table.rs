@@ -0,0 +1,210 @@
+use std::{mem, sync::atomic::{AtomicU64, Ordering}};
+
+use crate::{chess_move::{ChessMove, PackedMove}, score::Score};
+
+pub const DEFAULT_SIZE_MB: usize = 16;
+pub const MAX_SIZE_MB: usize = 4096;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Bound {
+    Exact,
+    //Fail high, the real value is at least the stored one
+    Lower,
+    //Fail low, the real value is at most the stored one
+    Upper,
+}
+
+impl Bound {
+    //Classifies a fail-hard search result against the window it was searched with
+    pub fn from_window(value: Score, alpha: Score, beta: Score) -> Bound {
+        if value >= beta {
+            return Bound::Lower;
+        }
+
+        if value <= alpha {
+            return Bound::Upper;
+        }
+
+        return Bound::Exact;
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct TTEntry {
+    pub best_move: PackedMove,
+    //Mate scores are relative to this position, see Score::to_tt
+    pub score: Score,
+    pub depth: u8,
+    pub bound: Bound,
+    age: u8,
+}
+
+//Set in every stored entry, a slot without it is empty
+const VALID_BIT: u64 = 1 << 63;
+
+impl TTEntry {
+    //Move in bits 0..16, score 16..32, depth 32..40, bound 40..42, age 42..50, valid bit 63
+    fn pack(&self) -> u64 {
+        let bound = match self.bound {
+            Bound::Exact => 0,
+            Bound::Lower => 1,
+            Bound::Upper => 2,
+        };
+
+        return self.best_move.get_value() as u64
+            | (self.score.get_value() as i16 as u16 as u64) << 16
+            | (self.depth as u64) << 32
+            | bound << 40
+            | (self.age as u64) << 42
+            | VALID_BIT;
+    }
+
+    fn unpack(data: u64) -> TTEntry {
+        let bound = match data >> 40 & 0x3 {
+            0 => Bound::Exact,
+            1 => Bound::Lower,
+            _ => Bound::Upper,
+        };
+
+        return TTEntry {
+            best_move: PackedMove::from_u16(data as u16),
+            score: Score::from_raw((data >> 16) as u16 as i16 as i32),
+            depth: (data >> 32) as u8,
+            bound,
+            age: (data >> 42) as u8
+        };
+    }
+
+    //Returns the score to use for the window alpha..beta at the given ply if the stored bound allows a cutoff
+    pub fn get_cutoff_score(&self, alpha: Score, beta: Score, ply: u8) -> Option<Score> {
+        let score = self.score.tt_to_search(ply);
+
+        return match self.bound {
+            Bound::Exact => Some(score),
+            Bound::Lower if score >= beta => Some(beta),
+            Bound::Upper if score <= alpha => Some(alpha),
+            _ => None,
+        };
+    }
+}
+
+//The key is stored xor the data, a slot torn by two threads writing at once no longer matches its hash and is ignored
+struct Slot {
+    key: AtomicU64,
+    data: AtomicU64,
+}
+
+//Shared by all search threads without locking
+pub struct TranspositionTable {
+    slots: Vec<Slot>,
+    age: u8,
+}
+
+impl TranspositionTable {
+    pub fn new(size_mb: usize) -> TranspositionTable {
+        let size_mb = size_mb.clamp(1, MAX_SIZE_MB);
+        let slot_count = size_mb * 1024 * 1024 / mem::size_of::<Slot>();
+
+        let slots = (0..slot_count).map(|_| Slot { key: AtomicU64::new(0), data: AtomicU64::new(0) }).collect();
+
+        return TranspositionTable { slots, age: 0 };
+    }
+
+    pub fn resize(&mut self, size_mb: usize) {
+        *self = TranspositionTable::new(size_mb);
+    }
+
+    pub fn clear(&mut self) {
+        for slot in self.slots.iter_mut() {
+            *slot.key.get_mut() = 0;
+            *slot.data.get_mut() = 0;
+        }
+
+        self.age = 0;
+    }
+
+    //Called once per search, entries of older searches are replaced first
+    pub fn new_search(&mut self) {
+        self.age = self.age.wrapping_add(1);
+    }
+
+    fn get_index(&self, hash: u64) -> usize {
+        return ((hash as u128 * self.slots.len() as u128) >> 64) as usize;
+    }
+
+    //Returns the stored hash and data of a slot, data without the valid bit marks an empty slot
+    fn load(&self, index: usize) -> (u64, u64) {
+        let slot = &self.slots[index];
+        let data = slot.data.load(Ordering::Relaxed);
+
+        return (slot.key.load(Ordering::Relaxed) ^ data, data);
+    }
+
+    pub fn probe(&self, hash: u64) -> Option<TTEntry> {
+        let (key, data) = self.load(self.get_index(hash));
+
+        if data & VALID_BIT != 0 && key == hash {
+            return Some(TTEntry::unpack(data));
+        }
+
+        return None;
+    }
+
+    pub fn store(&self, hash: u64, depth: u8, ply: u8, best_move: ChessMove, score: Score, bound: Bound) {
+        let index = self.get_index(hash);
+        let (key, data) = self.load(index);
+        let mut best_move = best_move.pack();
+
+        if data & VALID_BIT != 0 {
+            let old = TTEntry::unpack(data);
+
+            //Deeper results of the current search are kept
+            if old.age == self.age && depth < old.depth {
+                return;
+            }
+
+            //Fail lows have no best move, keep the one from an earlier search of this position
+            if best_move.is_null_move() && key == hash {
+                best_move = old.best_move;
+            }
+        }
+
+        let data = TTEntry { best_move, score: score.to_tt(ply), depth, bound, age: self.age }.pack();
+        let slot = &self.slots[index];
+
+        slot.data.store(data, Ordering::Relaxed);
+        slot.key.store(hash ^ data, Ordering::Relaxed);
+    }
+
+    //Used entries per thousand, estimated from the start of the table
+    pub fn hashfull(&self) -> u32 {
+        let sample = self.slots.len().min(1000);
+
+        let used = (0..sample)
+            .map(|i| self.load(i).1)
+            .filter(|data| *data & VALID_BIT != 0 && TTEntry::unpack(*data).age == self.age)
+            .count();
+
+        return (used * 1000 / sample) as u32;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chess_move::NULL_MOVE;
+
+    #[test]
+    fn entries_are_found_only_under_their_own_hash() {
+        let mut tt = TranspositionTable::new(1);
+        tt.clear();
+
+        //Every field of this entry packs to zero
+        tt.store(0, 0, 0, NULL_MOVE, Score::DRAW, Bound::Exact);
+        assert!(tt.probe(0).is_some_and(|entry| entry.depth == 0 && entry.best_move.is_null_move()));
+
+        //Same slot, different hash
+        assert!(tt.probe(1).is_none());
+        assert!(tt.probe(0x1234_5678).is_none());
+    }
+}